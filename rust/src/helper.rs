@@ -2,18 +2,36 @@
 // TTS Helper Module - All utility functions and structures
 // ============================================================================
 
-use ndarray::{Array, Array3};
+use ndarray::{s, Array, Array2, Array3, ArrayViewMut2};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use serde_json;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::io::BufReader;
-use std::path::Path;
+use std::io::{BufReader, BufWriter, Cursor, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 use anyhow::{Result, Context, bail};
 use unicode_normalization::UnicodeNormalization;
 use hound::{WavWriter, WavSpec, SampleFormat};
-use rand_distr::{Distribution, Normal};
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use rand::distributions::{Distribution, Uniform};
+#[cfg(feature = "rand_distr")]
+use rand_distr::Normal;
 use regex::Regex;
 
+pub use crate::math::{compute_lengths, get_text_mask, length_to_mask, LatentRounding, LengthError, LengthInfo, DEFAULT_MAX_LATENT_LEN};
+
+/// The common types and functions in one import: `use supertonic::prelude::*`
+pub mod prelude {
+    pub use super::{
+        load_text_to_speech, load_voice_style, write_wav_file, AudioBuffer, AudioStats, Backend, Config, LatentRounding,
+        LengthError, MemoryEstimate, OverlongPolicy, Style, TextToSpeech, VoiceLibrary,
+    };
+}
+
 // Available languages for multilingual TTS
 pub const AVAILABLE_LANGS: &[&str] = &["en", "ko", "es", "pt", "fr"];
 
@@ -43,12 +61,40 @@ pub struct TTLConfig {
     pub latent_dim: i32,
 }
 
+/// Read and deserialize a JSON file, naming the file and error position on failure
+pub fn read_json_file<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T> {
+    let path = path.as_ref();
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let reader = BufReader::new(file);
+    serde_json::from_reader(reader).map_err(|e| {
+        anyhow::anyhow!(
+            "Failed to parse {} at line {}, column {}: {}",
+            path.display(),
+            e.line(),
+            e.column(),
+            e
+        )
+    })
+}
+
+/// Deserialize JSON held in memory; `name` identifies its source in errors
+pub fn parse_json_slice<T: DeserializeOwned>(bytes: &[u8], name: &str) -> Result<T> {
+    serde_json::from_slice(bytes).map_err(|e| {
+        anyhow::anyhow!(
+            "Failed to parse {} at line {}, column {}: {}",
+            name,
+            e.line(),
+            e.column(),
+            e
+        )
+    })
+}
+
 /// Load configuration from JSON file
 pub fn load_cfgs<P: AsRef<Path>>(onnx_dir: P) -> Result<Config> {
     let cfg_path = onnx_dir.as_ref().join("tts.json");
-    let file = File::open(cfg_path)?;
-    let reader = BufReader::new(file);
-    let cfgs: Config = serde_json::from_reader(reader)?;
+    let cfgs: Config = read_json_file(cfg_path)?;
     Ok(cfgs)
 }
 
@@ -70,26 +116,332 @@ pub struct StyleComponent {
     pub dtype: String,
 }
 
+/// A raw 1D speaker embedding, optionally with projections onto the two style components
+///
+/// The JSON file holds either a bare array of numbers, or an object
+/// `{"embedding": [...], "ttl_projection": [[...]], "dp_projection": [[...]]}` in which each
+/// projection has one row per flattened (row-major) style value and one column per
+/// embedding value.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum SpeakerEmbedding {
+    Flat(Vec<f32>),
+    Projected {
+        embedding: Vec<f32>,
+        #[serde(default)]
+        ttl_projection: Option<Vec<Vec<f32>>>,
+        #[serde(default)]
+        dp_projection: Option<Vec<Vec<f32>>>,
+    },
+}
+
+impl SpeakerEmbedding {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        read_json_file(path)
+    }
+
+    pub fn embedding(&self) -> &[f32] {
+        match self {
+            SpeakerEmbedding::Flat(embedding) | SpeakerEmbedding::Projected { embedding, .. } => embedding,
+        }
+    }
+
+    /// Build a single-item `Style` with per-item shapes `ttl_dims` and `dp_dims`
+    ///
+    /// A component with a projection is its matrix times the embedding. Without one, the
+    /// embedding is tiled in row-major order, which requires the component size to be a
+    /// multiple of the embedding length (an exact match is a plain reshape). Tiling only
+    /// adapts the shape: it assumes the embedding was trained for that layout and does not
+    /// learn any mapping, so an arbitrary embedding will not produce a meaningful voice.
+    pub fn to_style(&self, ttl_dims: [usize; 2], dp_dims: [usize; 2]) -> Result<Style> {
+        let (ttl_projection, dp_projection) = match self {
+            SpeakerEmbedding::Flat(_) => (None, None),
+            SpeakerEmbedding::Projected { ttl_projection, dp_projection, .. } => {
+                (ttl_projection.as_deref(), dp_projection.as_deref())
+            }
+        };
+        let ttl = self.component("style_ttl", ttl_projection, ttl_dims[0] * ttl_dims[1])?;
+        let dp = self.component("style_dp", dp_projection, dp_dims[0] * dp_dims[1])?;
+        Style::from_slices(&ttl, [1, ttl_dims[0], ttl_dims[1]], &dp, [1, dp_dims[0], dp_dims[1]])
+    }
+
+    fn component(&self, name: &str, projection: Option<&[Vec<f32>]>, size: usize) -> Result<Vec<f32>> {
+        let embedding = self.embedding();
+        if embedding.is_empty() {
+            bail!("Speaker embedding is empty");
+        }
+        match projection {
+            Some(rows) => {
+                if rows.len() != size {
+                    bail!("{} projection has {} rows, but the style needs {}", name, rows.len(), size);
+                }
+                rows.iter()
+                    .map(|row| {
+                        if row.len() != embedding.len() {
+                            bail!(
+                                "{} projection rows have {} columns, but the embedding has {} values",
+                                name, row.len(), embedding.len()
+                            );
+                        }
+                        Ok(row.iter().zip(embedding).map(|(w, e)| w * e).sum())
+                    })
+                    .collect()
+            }
+            None => {
+                if !size.is_multiple_of(embedding.len()) {
+                    bail!(
+                        "Cannot tile a {}-value embedding into {} ({} values); provide a projection",
+                        embedding.len(), name, size
+                    );
+                }
+                Ok(embedding.iter().copied().cycle().take(size).collect())
+            }
+        }
+    }
+}
+
+/// Stack single-item styles into one batch
+pub fn concat_styles(styles: &[Style]) -> Result<Style> {
+    let ttl: Vec<_> = styles.iter().map(|s| s.ttl.view()).collect();
+    let dp: Vec<_> = styles.iter().map(|s| s.dp.view()).collect();
+    Ok(Style {
+        ttl: ndarray::concatenate(ndarray::Axis(0), &ttl)?,
+        dp: ndarray::concatenate(ndarray::Axis(0), &dp)?,
+    })
+}
+
+/// One voice of a `VoiceLibrary`: its style file and optional descriptive metadata
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoiceEntry {
+    pub path: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub gender: Option<String>,
+    #[serde(default)]
+    pub lang: Option<String>,
+}
+
+/// Voice styles addressed by short ids through an `index.json`
+///
+/// The index maps each id to a `VoiceEntry`, e.g. `{"M1": {"path": "M1.json", "name": "Mark",
+/// "gender": "male", "lang": "en"}}`. Relative paths are resolved against the index's
+/// directory; style files are only read when a `Style` is requested.
+pub struct VoiceLibrary {
+    root: PathBuf,
+    voices: BTreeMap<String, VoiceEntry>,
+}
+
+impl VoiceLibrary {
+    pub fn load<P: AsRef<Path>>(index_path: P) -> Result<Self> {
+        let index_path = index_path.as_ref();
+        let voices = read_json_file(index_path)?;
+        let root = index_path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(VoiceLibrary { root, voices })
+    }
+
+    /// Entries sorted by id
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &VoiceEntry)> {
+        self.voices.iter().map(|(id, entry)| (id.as_str(), entry))
+    }
+
+    pub fn get(&self, id: &str) -> Option<&VoiceEntry> {
+        self.voices.get(id)
+    }
+
+    /// Style file path of voice `id`
+    pub fn resolve(&self, id: &str) -> Result<PathBuf> {
+        let entry = self.voices.get(id).with_context(|| {
+            let known: Vec<&str> = self.voices.keys().map(String::as_str).collect();
+            format!("Unknown voice id '{}' (known: {})", id, known.join(", "))
+        })?;
+        Ok(self.root.join(&entry.path))
+    }
+
+    /// Load the styles of `ids` as one batch, in order
+    pub fn load_style(&self, ids: &[String], verbose: bool) -> Result<Style> {
+        let paths = ids
+            .iter()
+            .map(|id| self.resolve(id).map(|path| path.to_string_lossy().into_owned()))
+            .collect::<Result<Vec<_>>>()?;
+        load_voice_style(&paths, verbose)
+    }
+}
+
+// ============================================================================
+// Scripts
+// ============================================================================
+
+/// One line of a screenplay-style script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptLine {
+    /// Voice id from a leading `[voice:ID]` tag, if any
+    pub voice: Option<String>,
+    pub text: String,
+}
+
+/// Split a script into lines, each optionally starting with a `[voice:ID]` directive
+///
+/// Blank lines, and lines holding only a directive, are skipped. The directive must open
+/// the line; anything else in brackets is left in the text.
+pub fn parse_script(source: &str) -> Vec<ScriptLine> {
+    source
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| {
+            let directive = line
+                .strip_prefix("[voice:")
+                .and_then(|rest| rest.split_once(']'))
+                .map(|(id, text)| (id.trim(), text.trim()));
+            match directive {
+                // A directive with no text would be an empty utterance
+                Some((id, "")) if !id.is_empty() => None,
+                Some((id, text)) if !id.is_empty() => Some(ScriptLine {
+                    voice: Some(id.to_string()),
+                    text: text.to_string(),
+                }),
+                _ => Some(ScriptLine { voice: None, text: line.to_string() }),
+            }
+        })
+        .collect()
+}
+
 // ============================================================================
 // Unicode Text Processor
 // ============================================================================
 
+/// Intermediate results of mapping one text to model ids
+#[derive(Debug, Clone)]
+pub struct TextTrace {
+    /// Text after normalization and preprocessing, including language tags
+    pub processed: String,
+    pub codepoints: Vec<usize>,
+    /// Model ids, -1 for out-of-vocabulary characters
+    pub ids: Vec<i64>,
+}
+
+/// How to handle utterances in which no character maps to a valid id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnmappableTextPolicy {
+    /// Don't check
+    Allow,
+    /// Print a warning and synthesize anyway
+    #[default]
+    Warn,
+    /// Fail with an error
+    Strict,
+}
+
 pub struct UnicodeProcessor {
     indexer: Vec<i64>,
+    normalizer: Box<dyn TextNormalizer>,
+    skip_normalization: bool,
+    unmappable_policy: UnmappableTextPolicy,
+    out_of_range_id: Option<i64>,
 }
 
 impl UnicodeProcessor {
     pub fn new<P: AsRef<Path>>(unicode_indexer_json_path: P) -> Result<Self> {
-        let file = File::open(unicode_indexer_json_path)?;
-        let reader = BufReader::new(file);
-        let indexer: Vec<i64> = serde_json::from_reader(reader)?;
-        Ok(UnicodeProcessor { indexer })
+        let indexer: Vec<i64> = read_json_file(unicode_indexer_json_path)?;
+        Ok(Self::from_indexer(indexer))
+    }
+
+    /// Build a processor from an already-loaded codepoint-to-id table
+    pub fn from_indexer(indexer: Vec<i64>) -> Self {
+        UnicodeProcessor {
+            indexer,
+            normalizer: Box::new(NoopNormalizer),
+            skip_normalization: false,
+            unmappable_policy: UnmappableTextPolicy::default(),
+            out_of_range_id: None,
+        }
+    }
+
+    /// Map characters beyond the end of the indexer to `id` instead of -1 (OOV)
+    ///
+    /// Indexers sized for the Basic Multilingual Plane have no entry for astral-plane
+    /// characters such as emoji above U+FFFF; this lets them map to e.g. a space or
+    /// unknown-symbol id. Characters inside the table keep their entry, even if it is -1.
+    pub fn set_out_of_range_id(&mut self, id: Option<i64>) {
+        self.out_of_range_id = id;
+    }
+
+    /// Set how utterances with no mappable characters are handled (default: warn)
+    pub fn set_unmappable_policy(&mut self, policy: UnmappableTextPolicy) {
+        self.unmappable_policy = policy;
+    }
+
+    /// Skip NFKD and symbol cleanup for text that is already normalized;
+    /// only the language tags are added
+    pub fn set_skip_normalization(&mut self, skip: bool) {
+        self.skip_normalization = skip;
+    }
+
+    /// Replace the normalizer applied before `preprocess_text` (default: no-op)
+    pub fn set_normalizer(&mut self, normalizer: Box<dyn TextNormalizer>) {
+        self.normalizer = normalizer;
+    }
+
+    /// Normalize and preprocess one text into the string that gets mapped to ids
+    pub fn process_text(&self, text: &str, lang: &str) -> Result<String> {
+        let normalized = self.normalizer.normalize(text, lang);
+        if self.skip_normalization {
+            wrap_with_lang(&normalized, lang)
+        } else {
+            preprocess_text(&normalized, lang)
+        }
+    }
+
+    /// Overlay `patch` (codepoint, id) entries onto the indexer; see `merge_indexer`
+    pub fn merge_indexer(&mut self, patch: &[(usize, i64)]) {
+        self.indexer = merge_indexer(&self.indexer, patch);
+    }
+
+    /// Map a single unicode value to its model id (-1 when out of vocabulary)
+    pub fn map_value(&self, val: usize) -> i64 {
+        if val < self.indexer.len() {
+            self.indexer[val]
+        } else {
+            self.out_of_range_id.unwrap_or(-1)
+        }
+    }
+
+    /// Number of codepoints covered by the indexer table
+    pub fn indexer_len(&self) -> usize {
+        self.indexer.len()
+    }
+
+    /// Highest codepoint with a table entry (None for an empty indexer)
+    ///
+    /// Entries at or below it may still be -1; use `contains` to check a character.
+    pub fn max_codepoint(&self) -> Option<u32> {
+        self.indexer.len().checked_sub(1).map(|max| max as u32)
+    }
+
+    /// Whether `c` maps to a valid id, honouring `set_out_of_range_id`
+    ///
+    /// Checks the raw character; normalization may still rewrite it before mapping.
+    pub fn contains(&self, c: char) -> bool {
+        self.map_value(c as usize) >= 0
+    }
+
+    /// Show every stage of the mapping for one text, for debugging the indexer
+    pub fn trace(&self, text: &str, lang: &str) -> Result<TextTrace> {
+        let processed = self.process_text(text, lang)?;
+        let codepoints = text_to_unicode_values(&processed);
+        let ids = codepoints.iter().map(|&val| self.map_value(val)).collect();
+        Ok(TextTrace {
+            processed,
+            codepoints,
+            ids,
+        })
     }
 
     pub fn call(&self, text_list: &[String], lang_list: &[String]) -> Result<(Vec<Vec<i64>>, Array3<f32>)> {
         let mut processed_texts: Vec<String> = Vec::new();
         for (text, lang) in text_list.iter().zip(lang_list.iter()) {
-            processed_texts.push(preprocess_text(text, lang)?);
+            processed_texts.push(self.process_text(text, lang)?);
         }
 
         let text_ids_lengths: Vec<usize> = processed_texts
@@ -104,15 +456,29 @@ impl UnicodeProcessor {
             let mut row = vec![0i64; max_len];
             let unicode_vals = text_to_unicode_values(text);
             for (j, &val) in unicode_vals.iter().enumerate() {
-                if val < self.indexer.len() {
-                    row[j] = self.indexer[val];
-                } else {
-                    row[j] = -1;
-                }
+                row[j] = self.map_value(val);
             }
             text_ids.push(row);
         }
 
+        if self.unmappable_policy != UnmappableTextPolicy::Allow {
+            for (i, (row, lang)) in text_ids.iter().zip(lang_list.iter()).enumerate() {
+                // Ignore the <lang>...</lang> tags, which always map
+                let start = (lang.len() + 2).min(text_ids_lengths[i]);
+                let end = text_ids_lengths[i].saturating_sub(lang.len() + 3).max(start);
+                if row[start..end].iter().all(|&id| id < 0) {
+                    let message = format!(
+                        "No character of text {} ({:?}) maps to a valid id; the output will be meaningless",
+                        i, text_list[i]
+                    );
+                    if self.unmappable_policy == UnmappableTextPolicy::Strict {
+                        bail!(message);
+                    }
+                    eprintln!("Warning: {}", message);
+                }
+            }
+        }
+
         let text_mask = get_text_mask(&text_ids_lengths);
 
         Ok((text_ids, text_mask))
@@ -201,77 +567,507 @@ pub fn preprocess_text(text: &str, lang: &str) -> Result<String> {
         }
     }
 
-    // Validate language
+    wrap_with_lang(&text, lang)
+}
+
+/// Validate `lang` and wrap text with its language tags
+pub fn wrap_with_lang(text: &str, lang: &str) -> Result<String> {
     if !is_valid_lang(lang) {
         bail!("Invalid language: {}. Available: {:?}", lang, AVAILABLE_LANGS);
     }
 
-    // Wrap text with language tags
-    text = format!("<{}>{}</{}>", lang, text, lang);
+    Ok(format!("<{}>{}</{}>", lang, text, lang))
+}
+
+// ============================================================================
+// Text Normalization
+// ============================================================================
+
+/// Rewrites raw text (numbers, symbols, abbreviations) into speakable words
+/// before `preprocess_text` runs
+pub trait TextNormalizer: Send + Sync {
+    fn normalize(&self, text: &str, lang: &str) -> String;
+}
+
+/// Default normalizer: passes text through unchanged
+pub struct NoopNormalizer;
+
+impl TextNormalizer for NoopNormalizer {
+    fn normalize(&self, text: &str, _lang: &str) -> String {
+        text.to_string()
+    }
+}
+
+/// Basic English normalizer expanding numbers, currency, percentages,
+/// fractions, ordinals and common abbreviations. Other languages pass through.
+pub struct EnglishNormalizer;
+
+const ONES: &[&str] = &[
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+    "ten", "eleven", "twelve", "thirteen", "fourteen", "fifteen", "sixteen",
+    "seventeen", "eighteen", "nineteen",
+];
+
+const TENS: &[&str] = &[
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+
+const SCALES: &[(u64, &str)] = &[
+    (1_000_000_000_000, "trillion"),
+    (1_000_000_000, "billion"),
+    (1_000_000, "million"),
+    (1_000, "thousand"),
+];
+
+const ABBREVIATION_EXPANSIONS: &[(&str, &str)] = &[
+    ("Dr.", "Doctor"),
+    ("Mr.", "Mister"),
+    ("Mrs.", "Missus"),
+    ("Ms.", "Mizz"),
+    ("Prof.", "Professor"),
+    ("Jr.", "Junior"),
+    ("Sr.", "Senior"),
+    ("vs.", "versus"),
+    ("etc.", "et cetera"),
+    ("approx.", "approximately"),
+];
+
+/// Spell out a non-negative integer in English words
+pub fn number_to_words(n: u64) -> String {
+    if n < 20 {
+        return ONES[n as usize].to_string();
+    }
+    if n < 100 {
+        let tens = TENS[(n / 10) as usize];
+        return match n % 10 {
+            0 => tens.to_string(),
+            r => format!("{}-{}", tens, ONES[r as usize]),
+        };
+    }
+    if n < 1000 {
+        let head = format!("{} hundred", ONES[(n / 100) as usize]);
+        return match n % 100 {
+            0 => head,
+            r => format!("{} {}", head, number_to_words(r)),
+        };
+    }
+    for &(scale, name) in SCALES {
+        if n >= scale {
+            let head = format!("{} {}", number_to_words(n / scale), name);
+            return match n % scale {
+                0 => head,
+                r => format!("{} {}", head, number_to_words(r)),
+            };
+        }
+    }
+    unreachable!()
+}
+
+/// Spell out an ordinal ("first", "twenty-second", ...)
+pub fn ordinal_to_words(n: u64) -> String {
+    let cardinal = number_to_words(n);
+    let (head, last) = match cardinal.rfind([' ', '-']) {
+        Some(pos) => cardinal.split_at(pos + 1),
+        None => ("", cardinal.as_str()),
+    };
+    let last = match last {
+        "one" => "first".to_string(),
+        "two" => "second".to_string(),
+        "three" => "third".to_string(),
+        "five" => "fifth".to_string(),
+        "eight" => "eighth".to_string(),
+        "nine" => "ninth".to_string(),
+        "twelve" => "twelfth".to_string(),
+        w if w.ends_with('y') => format!("{}ieth", &w[..w.len() - 1]),
+        w => format!("{}th", w),
+    };
+    format!("{}{}", head, last)
+}
+
+/// An integer with well-formed thousands separators ("1,200") or none ("1200")
+///
+/// A comma that does not start a full group of three is punctuation, so "1,2,3" and
+/// "1990,2000" are several numbers rather than one.
+const GROUPED_INTEGER: &str = r"(?:\d{1,3}(?:,\d{3})+\b|\d+)";
+
+fn parse_grouped(digits: &str) -> Option<u64> {
+    digits.replace(',', "").parse().ok()
+}
+
+fn digits_to_words(digits: &str) -> String {
+    digits
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .map(|d| ONES[d as usize])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl TextNormalizer for EnglishNormalizer {
+    fn normalize(&self, text: &str, lang: &str) -> String {
+        if lang != "en" {
+            return text.to_string();
+        }
+
+        let mut text = text.to_string();
+
+        // Currency: $42.50 -> forty-two dollars and fifty cents
+        static CURRENCY_RE: OnceLock<Regex> = OnceLock::new();
+        let currency_re = CURRENCY_RE
+            .get_or_init(|| Regex::new(&format!(r"([$£€])({})(?:\.(\d{{1,2}}))?\b", GROUPED_INTEGER)).unwrap());
+        text = currency_re
+            .replace_all(&text, |caps: &regex::Captures| {
+                let (major, minor) = match &caps[1] {
+                    "$" => ("dollar", "cent"),
+                    "£" => ("pound", "penny"),
+                    _ => ("euro", "cent"),
+                };
+                let Some(whole) = parse_grouped(&caps[2]) else {
+                    return caps[0].to_string();
+                };
+                let mut out = format!(
+                    "{} {}{}",
+                    number_to_words(whole),
+                    major,
+                    if whole == 1 { "" } else { "s" }
+                );
+                if let Some(frac) = caps.get(3) {
+                    let cents: u64 = format!("{:0<2}", frac.as_str()).parse().unwrap_or(0);
+                    if cents > 0 {
+                        let minor = match (minor, cents) {
+                            ("penny", 1) => "penny".to_string(),
+                            ("penny", _) => "pence".to_string(),
+                            (m, 1) => m.to_string(),
+                            (m, _) => format!("{}s", m),
+                        };
+                        out = format!("{} and {} {}", out, number_to_words(cents), minor);
+                    }
+                }
+                out
+            })
+            .to_string();
+
+        // Percentages: 15% -> fifteen percent
+        static PERCENT_RE: OnceLock<Regex> = OnceLock::new();
+        let percent_re = PERCENT_RE.get_or_init(|| Regex::new(&format!(r"({}(?:\.\d+)?)%", GROUPED_INTEGER)).unwrap());
+        text = percent_re.replace_all(&text, "$1 percent").to_string();
+
+        // Fractions: 3/4 -> three fourths, 1/2 -> one half
+        static FRACTION_RE: OnceLock<Regex> = OnceLock::new();
+        let fraction_re = FRACTION_RE.get_or_init(|| Regex::new(r"\b(\d+)/(\d+)\b").unwrap());
+        text = fraction_re
+            .replace_all(&text, |caps: &regex::Captures| {
+                let (Ok(num), Ok(den)) = (caps[1].parse::<u64>(), caps[2].parse::<u64>()) else {
+                    return caps[0].to_string();
+                };
+                if den == 0 {
+                    return caps[0].to_string();
+                }
+                let den_word = match den {
+                    1 => return number_to_words(num),
+                    2 => "half".to_string(),
+                    d => ordinal_to_words(d),
+                };
+                let den_word = match (num, den) {
+                    (1, _) => den_word,
+                    (_, 2) => "halves".to_string(),
+                    _ => format!("{}s", den_word),
+                };
+                format!("{} {}", number_to_words(num), den_word)
+            })
+            .to_string();
+
+        // Ordinals: 21st -> twenty-first
+        static ORDINAL_RE: OnceLock<Regex> = OnceLock::new();
+        let ordinal_re = ORDINAL_RE.get_or_init(|| Regex::new(r"\b(\d+)(?:st|nd|rd|th)\b").unwrap());
+        text = ordinal_re
+            .replace_all(&text, |caps: &regex::Captures| match caps[1].parse::<u64>() {
+                Ok(n) => ordinal_to_words(n),
+                Err(_) => caps[0].to_string(),
+            })
+            .to_string();
+
+        // Decimals and integers: 3.14 -> three point one four, 1,200 -> one thousand two hundred
+        static NUMBER_RE: OnceLock<Regex> = OnceLock::new();
+        let number_re = NUMBER_RE.get_or_init(|| Regex::new(&format!(r"{}(?:\.\d+)?", GROUPED_INTEGER)).unwrap());
+        text = number_re
+            .replace_all(&text, |caps: &regex::Captures| {
+                let matched = &caps[0];
+                let (whole, frac) = match matched.split_once('.') {
+                    Some((w, f)) => (w, Some(f)),
+                    None => (matched, None),
+                };
+                let Some(whole) = parse_grouped(whole) else {
+                    return caps[0].to_string();
+                };
+                let mut out = number_to_words(whole);
+                if let Some(frac) = frac {
+                    out = format!("{} point {}", out, digits_to_words(frac));
+                }
+                out
+            })
+            .to_string();
+
+        for (abbrev, expansion) in ABBREVIATION_EXPANSIONS {
+            text = text.replace(abbrev, expansion);
+        }
+
+        text
+    }
+}
+
+/// Combine a base indexer with a small patch of (codepoint, id) entries
+///
+/// The patch wins on conflicts (and a later patch entry wins over an earlier one for the
+/// same codepoint). The table grows as needed, with -1 for any codepoints in between.
+pub fn merge_indexer(base: &[i64], patch: &[(usize, i64)]) -> Vec<i64> {
+    let len = patch
+        .iter()
+        .map(|&(codepoint, _)| codepoint + 1)
+        .max()
+        .unwrap_or(0)
+        .max(base.len());
+    let mut merged = base.to_vec();
+    merged.resize(len, -1);
+    for &(codepoint, id) in patch {
+        merged[codepoint] = id;
+    }
+    merged
+}
 
-    Ok(text)
+/// Read an indexer patch: a JSON object from a character or `U+XXXX` to its id
+pub fn load_indexer_patch<P: AsRef<Path>>(path: P) -> Result<Vec<(usize, i64)>> {
+    let path = path.as_ref();
+    let entries: std::collections::BTreeMap<String, i64> = read_json_file(path)?;
+    entries
+        .into_iter()
+        .map(|(key, id)| {
+            let mut chars = key.chars();
+            let codepoint = match (chars.next(), chars.next()) {
+                (Some(c), None) => c as usize,
+                _ => key
+                    .strip_prefix("U+")
+                    .and_then(|hex| usize::from_str_radix(hex, 16).ok())
+                    .with_context(|| {
+                        format!("{}: key {:?} is neither a single character nor U+XXXX", path.display(), key)
+                    })?,
+            };
+            Ok((codepoint, id))
+        })
+        .collect()
 }
 
+/// Codepoints of `text`
+///
+/// A Rust `char` is always a Unicode scalar value (never a lone surrogate), so the cast is
+/// lossless. Astral-plane characters (above U+FFFF, e.g. most emoji) yield values past the
+/// end of a BMP-sized indexer and map to -1 unless `set_out_of_range_id` is used.
 pub fn text_to_unicode_values(text: &str) -> Vec<usize> {
     text.chars().map(|c| c as usize).collect()
 }
 
-pub fn length_to_mask(lengths: &[usize], max_len: Option<usize>) -> Array3<f32> {
-    let bsz = lengths.len();
-    let max_len = max_len.unwrap_or_else(|| *lengths.iter().max().unwrap_or(&0));
-
-    let mut mask = Array3::<f32>::zeros((bsz, 1, max_len));
-    for (i, &len) in lengths.iter().enumerate() {
-        for j in 0..len.min(max_len) {
-            mask[[i, 0, j]] = 1.0;
+/// Like `length_to_mask`, but errors instead of silently truncating a length
+/// that exceeds `max_len`
+pub fn length_to_mask_checked(lengths: &[usize], max_len: Option<usize>) -> Result<Array3<f32>> {
+    if let Some(max_len) = max_len {
+        if let Some((i, &len)) = lengths.iter().enumerate().find(|(_, &len)| len > max_len) {
+            bail!("Length {} at index {} exceeds max_len {}", len, i, max_len);
         }
     }
-    mask
+    Ok(length_to_mask(lengths, max_len))
 }
 
-pub fn get_text_mask(text_ids_lengths: &[usize]) -> Array3<f32> {
-    let max_len = *text_ids_lengths.iter().max().unwrap_or(&0);
-    length_to_mask(text_ids_lengths, Some(max_len))
+/// Reduce duration-predictor output to one value per batch item
+///
+/// Accepts one value per utterance (`[bsz]` or `[bsz, 1]`) or per-token
+/// durations (`[bsz, text_len]`), which are summed over the unmasked tokens.
+pub fn per_utterance_duration(shape: &[i64], data: &[f32], text_mask: &Array3<f32>) -> Result<Vec<f32>> {
+    let bsz = text_mask.shape()[0];
+    let text_len = text_mask.shape()[2];
+
+    if data.len() == bsz {
+        return Ok(data.to_vec());
+    }
+
+    if shape.len() == 2 && shape[0] as usize == bsz && shape[1] as usize == text_len {
+        return Ok((0..bsz)
+            .map(|b| {
+                (0..text_len)
+                    .map(|t| data[b * text_len + t] * text_mask[[b, 0, t]])
+                    .sum()
+            })
+            .collect());
+    }
+
+    bail!(
+        "Unexpected duration predictor output shape {:?}: expected [{}] or [{}, {}]",
+        shape,
+        bsz,
+        bsz,
+        text_len
+    )
 }
 
 /// Sample noisy latent from normal distribution and apply mask
+///
+/// Noise is drawn from `rng` when provided, otherwise from `rand::thread_rng()`. Fails when a
+/// duration needs more than `max_latent_len` frames.
+#[allow(clippy::too_many_arguments)]
 pub fn sample_noisy_latent(
     duration: &[f32],
     sample_rate: i32,
     base_chunk_size: i32,
     chunk_compress: i32,
     latent_dim: i32,
-) -> (Array3<f32>, Array3<f32>) {
-    let bsz = duration.len();
-    let max_dur = duration.iter().fold(0.0f32, |a, &b| a.max(b));
+    rounding: LatentRounding,
+    max_latent_len: usize,
+    rng: Option<&mut dyn RngCore>,
+) -> Result<(Array3<f32>, Array3<f32>)> {
+    let mut normal = NormalSampler::new();
+    let mut thread_rng = rand::thread_rng();
+    let rng: &mut dyn RngCore = match rng {
+        Some(rng) => rng,
+        None => &mut thread_rng,
+    };
 
-    let wav_len_max = (max_dur * sample_rate as f32) as usize;
-    let wav_lengths: Vec<usize> = duration
-        .iter()
-        .map(|&d| (d * sample_rate as f32) as usize)
-        .collect();
+    masked_noise(duration, sample_rate, base_chunk_size, chunk_compress, latent_dim, rounding, max_latent_len, |_, mut item, _| {
+        item.iter_mut().for_each(|v| *v = normal.sample(rng));
+    })
+}
 
-    let chunk_size = (base_chunk_size * chunk_compress) as usize;
-    let latent_len = (wav_len_max + chunk_size - 1) / chunk_size;
-    let latent_dim_val = (latent_dim * chunk_compress) as usize;
+/// Like `sample_noisy_latent`, but each batch item draws from its own RNG
+///
+/// Item `i` is seeded with `derive_item_seed(seed, i)`, so its noise depends only on
+/// the master seed and its index, not on the other items in the batch.
+#[allow(clippy::too_many_arguments)]
+pub fn sample_noisy_latent_per_item(
+    duration: &[f32],
+    sample_rate: i32,
+    base_chunk_size: i32,
+    chunk_compress: i32,
+    latent_dim: i32,
+    rounding: LatentRounding,
+    max_latent_len: usize,
+    seed: u64,
+) -> Result<(Array3<f32>, Array3<f32>)> {
+    masked_noise(duration, sample_rate, base_chunk_size, chunk_compress, latent_dim, rounding, max_latent_len, |b, mut item, len| {
+        // Only the item's own frames are drawn, so the padding to the batch's longest
+        // item does not shift its stream
+        let mut normal = NormalSampler::new();
+        let mut rng = StdRng::seed_from_u64(derive_item_seed(seed, b));
+        item.slice_mut(s![.., ..len]).iter_mut().for_each(|v| *v = normal.sample(&mut rng));
+    })
+}
 
-    let mut noisy_latent = Array3::<f32>::zeros((bsz, latent_dim_val, latent_len));
+/// Standard normal sampler for the initial noise
+///
+/// Uses `rand_distr::Normal` (ziggurat) by default. Without the `rand_distr` feature it
+/// falls back to [`BoxMuller`] over `rand`'s uniform source: the same N(0, 1)
+/// distribution, but not the same values for a given seed. Matching the ziggurat
+/// value-for-value would mean reproducing its lookup tables and rejection loop, which
+/// is the dependency the fallback exists to drop, so seeded output is only
+/// reproducible between builds with the same feature set.
+struct NormalSampler {
+    #[cfg(feature = "rand_distr")]
+    normal: Normal<f32>,
+    #[cfg(not(feature = "rand_distr"))]
+    normal: BoxMuller,
+}
 
-    let normal = Normal::new(0.0, 1.0).unwrap();
-    let mut rng = rand::thread_rng();
+impl NormalSampler {
+    fn new() -> Self {
+        NormalSampler {
+            #[cfg(feature = "rand_distr")]
+            normal: Normal::new(0.0, 1.0).unwrap(),
+            #[cfg(not(feature = "rand_distr"))]
+            normal: BoxMuller::default(),
+        }
+    }
 
-    for b in 0..bsz {
-        for d in 0..latent_dim_val {
-            for t in 0..latent_len {
-                noisy_latent[[b, d, t]] = normal.sample(&mut rng);
-            }
+    #[cfg(feature = "rand_distr")]
+    fn sample(&mut self, rng: &mut dyn RngCore) -> f32 {
+        self.normal.sample(rng)
+    }
+
+    #[cfg(not(feature = "rand_distr"))]
+    fn sample(&mut self, rng: &mut dyn RngCore) -> f32 {
+        self.normal.sample(rng)
+    }
+}
+
+/// Box–Muller standard normal sampler, the fallback without `rand_distr`
+#[derive(Default)]
+#[cfg_attr(feature = "rand_distr", allow(dead_code))]
+struct BoxMuller {
+    /// Second value of the last pair
+    spare: Option<f32>,
+}
+
+#[cfg_attr(feature = "rand_distr", allow(dead_code))]
+impl BoxMuller {
+    fn sample(&mut self, rng: &mut dyn RngCore) -> f32 {
+        use rand::Rng;
+
+        if let Some(z) = self.spare.take() {
+            return z;
         }
+        // u1 in (0, 1] keeps the logarithm finite
+        let u1 = 1.0 - rng.gen::<f64>();
+        let u2 = rng.gen::<f64>();
+        let radius = (-2.0 * u1.ln()).sqrt();
+        let (sin, cos) = (2.0 * std::f64::consts::PI * u2).sin_cos();
+        self.spare = Some((radius * sin) as f32);
+        (radius * cos) as f32
     }
+}
 
-    let latent_lengths: Vec<usize> = wav_lengths
-        .iter()
-        .map(|&len| (len + chunk_size - 1) / chunk_size)
-        .collect();
+/// Reject a zero denoising step count
+///
+/// Zero steps would hand the raw noise straight to the vocoder.
+pub fn check_total_step(total_step: usize) -> Result<()> {
+    if total_step == 0 {
+        bail!("total_step must be at least 1; zero denoising steps produce pure noise");
+    }
+    Ok(())
+}
+
+/// Per-item seed: `seed ^ splitmix64(index)`
+///
+/// SplitMix64 scatters consecutive indices across all 64 bits, so neighbouring
+/// items get unrelated streams.
+pub fn derive_item_seed(seed: u64, index: usize) -> u64 {
+    let mut z = (index as u64).wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    seed ^ (z ^ (z >> 31))
+}
+
+/// Allocate the batch latent, let `fill` write each item's (latent_dim, latent_len) noise, then mask
+///
+/// `fill` also gets the item's unpadded latent length; frames beyond it are masked to zero.
+#[allow(clippy::too_many_arguments)]
+fn masked_noise(
+    duration: &[f32],
+    sample_rate: i32,
+    base_chunk_size: i32,
+    chunk_compress: i32,
+    latent_dim: i32,
+    rounding: LatentRounding,
+    max_latent_len: usize,
+    mut fill: impl FnMut(usize, ArrayViewMut2<f32>, usize),
+) -> Result<(Array3<f32>, Array3<f32>)> {
+    let bsz = duration.len();
+    let LengthInfo {
+        latent_dim: latent_dim_val,
+        latent_len,
+        latent_lengths,
+        ..
+    } = compute_lengths(duration, sample_rate, base_chunk_size, chunk_compress, latent_dim, rounding, max_latent_len)?;
+
+    let mut noisy_latent = Array3::<f32>::zeros((bsz, latent_dim_val, latent_len));
+    for (b, item) in noisy_latent.outer_iter_mut().enumerate() {
+        fill(b, item, latent_lengths[b]);
+    }
 
     let latent_mask = length_to_mask(&latent_lengths, Some(latent_len));
 
@@ -284,50 +1080,968 @@ pub fn sample_noisy_latent(
         }
     }
 
-    (noisy_latent, latent_mask)
+    Ok((noisy_latent, latent_mask))
 }
 
 // ============================================================================
 // WAV File I/O
 // ============================================================================
 
-pub fn write_wav_file<P: AsRef<Path>>(
-    filename: P,
-    audio_data: &[f32],
-    sample_rate: i32,
-) -> Result<()> {
-    let spec = WavSpec {
-        channels: 1,
-        sample_rate: sample_rate as u32,
-        bits_per_sample: 16,
-        sample_format: SampleFormat::Int,
-    };
+/// Full-scale mapping used when quantizing f32 samples to integer PCM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PcmScaling {
+    /// Scale by 2^(bits-1) - 1 so +1.0 and -1.0 map symmetrically (e.g. ±32767)
+    #[default]
+    Symmetric,
+    /// Scale by 2^(bits-1) and clamp, using the full range (e.g. -32768..=32767)
+    Full,
+}
 
-    let mut writer = WavWriter::create(filename, spec)?;
+/// How scaled samples are turned into integers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PcmRounding {
+    /// Drop the fractional part (round toward zero)
+    #[default]
+    Truncate,
+    /// Round to the nearest integer, halves away from zero
+    Nearest,
+}
 
-    for &sample in audio_data {
-        let clamped = sample.max(-1.0).min(1.0);
-        let val = (clamped * 32767.0) as i16;
-        writer.write_sample(val)?;
-    }
+/// Quantization settings for integer PCM output
+///
+/// The default (symmetric scaling, truncation) matches the historical output;
+/// `Full` + `Nearest` matches implementations that scale by 32768 and round.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PcmOptions {
+    pub scaling: PcmScaling,
+    pub rounding: PcmRounding,
+}
 
-    writer.finalize()?;
-    Ok(())
+/// Quantize one sample in [-1, 1] to a signed `bit_depth`-bit integer
+fn quantize(sample: f32, bit_depth: u16, options: PcmOptions) -> i32 {
+    let full = (1u64 << (bit_depth - 1)) as f64;
+    let scale = match options.scaling {
+        PcmScaling::Symmetric => full - 1.0,
+        PcmScaling::Full => full,
+    };
+    let sample = sample.clamp(-1.0, 1.0);
+    // Up to 24 bits the product is exact enough in f32, which the original converters used
+    let scaled = if bit_depth <= 24 {
+        (sample * scale as f32) as f64
+    } else {
+        sample as f64 * scale
+    };
+    let rounded = match options.rounding {
+        PcmRounding::Truncate => scaled.trunc(),
+        PcmRounding::Nearest => scaled.round(),
+    };
+    rounded.clamp(-full, full - 1.0) as i32
 }
 
-// ============================================================================
-// Text Chunking
-// ============================================================================
+/// Convert f32 samples to signed `bit_depth`-bit PCM stored in i32
+pub fn f32_to_pcm(audio: &[f32], bit_depth: u16, options: PcmOptions) -> Vec<i32> {
+    audio
+        .iter()
+        .map(|&sample| quantize(sample, bit_depth, options))
+        .collect()
+}
 
-const MAX_CHUNK_LENGTH: usize = 300;
+/// Convert f32 samples in [-1, 1] to 16-bit PCM (clamped, scaled by 32767)
+pub fn f32_to_i16(audio: &[f32]) -> Vec<i16> {
+    audio
+        .iter()
+        .map(|&sample| quantize(sample, 16, PcmOptions::default()) as i16)
+        .collect()
+}
 
-const ABBREVIATIONS: &[&str] = &[
-    "Dr.", "Mr.", "Mrs.", "Ms.", "Prof.", "Sr.", "Jr.",
-    "St.", "Ave.", "Rd.", "Blvd.", "Dept.", "Inc.", "Ltd.",
-    "Co.", "Corp.", "etc.", "vs.", "i.e.", "e.g.", "Ph.D.",
-];
+/// Convert f32 samples in [-1, 1] to 24-bit PCM stored in i32 (clamped, scaled by 8388607)
+pub fn f32_to_i24(audio: &[f32]) -> Vec<i32> {
+    f32_to_pcm(audio, 24, PcmOptions::default())
+}
 
-pub fn chunk_text(text: &str, max_len: Option<usize>) -> Vec<String> {
+/// Convert f32 samples in [-1, 1] to 32-bit PCM (clamped, scaled by 2147483647)
+pub fn f32_to_i32(audio: &[f32]) -> Vec<i32> {
+    f32_to_pcm(audio, 32, PcmOptions::default())
+}
+
+/// Add TPDF dither of ±1 LSB at `bit_depth` to decorrelate quantization noise
+///
+/// Apply just before integer conversion. Noise is drawn from `rng` when provided,
+/// otherwise from `rand::thread_rng()`.
+pub fn tpdf_dither(audio: &mut [f32], bit_depth: u16, rng: Option<&mut dyn RngCore>) {
+    let lsb = 1.0 / ((1u64 << (bit_depth - 1)) - 1) as f32;
+    let mut thread_rng = rand::thread_rng();
+    let rng: &mut dyn RngCore = match rng {
+        Some(rng) => rng,
+        None => &mut thread_rng,
+    };
+
+    // The difference of two uniform variables in [0, 1) is triangular on (-1, 1)
+    let uniform = Uniform::new(0.0f32, 1.0);
+    for sample in audio.iter_mut() {
+        *sample += (uniform.sample(rng) - uniform.sample(rng)) * lsb;
+    }
+}
+
+/// Write mono PCM WAV data at the given bit depth (16, 24 or 32) to any seekable writer
+pub fn write_wav<W: Write + Seek>(
+    writer: W,
+    audio_data: &[f32],
+    sample_rate: i32,
+    bit_depth: u16,
+) -> Result<()> {
+    write_wav_with_options(writer, audio_data, sample_rate, bit_depth, PcmOptions::default())
+}
+
+/// Like `write_wav`, with explicit quantization settings
+pub fn write_wav_with_options<W: Write + Seek>(
+    writer: W,
+    audio_data: &[f32],
+    sample_rate: i32,
+    bit_depth: u16,
+    options: PcmOptions,
+) -> Result<()> {
+    write_pcm(writer, audio_data, sample_rate as u32, 1, bit_depth, options)
+}
+
+/// Write interleaved samples as integer PCM WAV data
+fn write_pcm<W: Write + Seek>(
+    writer: W,
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u16,
+    bit_depth: u16,
+    options: PcmOptions,
+) -> Result<()> {
+    if !matches!(bit_depth, 16 | 24 | 32) {
+        bail!("Unsupported bit depth: {} (expected 16, 24 or 32)", bit_depth);
+    }
+
+    let spec = WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: bit_depth,
+        sample_format: SampleFormat::Int,
+    };
+
+    let mut writer = WavWriter::new(writer, spec)?;
+    for val in f32_to_pcm(samples, bit_depth, options) {
+        writer.write_sample(val)?;
+    }
+
+    writer.finalize()?;
+    Ok(())
+}
+
+/// Samples together with the rate and channel layout needed to interpret them
+///
+/// Multi-channel audio is interleaved (channel 0 first in each frame).
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioBuffer {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl AudioBuffer {
+    pub fn mono(samples: Vec<f32>, sample_rate: u32) -> Self {
+        AudioBuffer { samples, sample_rate, channels: 1 }
+    }
+
+    /// Number of frames (samples per channel)
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+
+    pub fn duration_secs(&self) -> f32 {
+        if self.sample_rate == 0 {
+            return 0.0;
+        }
+        self.frames() as f32 / self.sample_rate as f32
+    }
+
+    /// Largest absolute sample value over all channels
+    pub fn peak(&self) -> f32 {
+        self.samples.iter().fold(0.0f32, |peak, &s| peak.max(s.abs()))
+    }
+
+    /// Resample (linear) to `sample_rate`; mono only
+    pub fn resampled(&self, sample_rate: u32) -> Result<AudioBuffer> {
+        if self.channels != 1 {
+            bail!("Resampling supports mono audio only, got {} channels", self.channels);
+        }
+        Ok(AudioBuffer::mono(resample_linear(&self.samples, self.sample_rate, sample_rate), sample_rate))
+    }
+
+    /// Encode as a complete in-memory PCM WAV file at the given bit depth (16, 24 or 32)
+    pub fn to_wav_bytes(&self, bit_depth: u16) -> Result<Vec<u8>> {
+        let mut cursor = Cursor::new(Vec::new());
+        write_pcm(&mut cursor, &self.samples, self.sample_rate, self.channels, bit_depth, PcmOptions::default())?;
+        Ok(cursor.into_inner())
+    }
+}
+
+/// Size in bytes of the WAV file `write_wav_file` and friends produce for `sample_count`
+/// samples per channel
+///
+/// The header is 44 bytes, or 68 when hound needs WAVE_FORMAT_EXTENSIBLE (more than two
+/// channels or more than 16 bits). Extra chunks such as `bext` are not included.
+pub fn estimate_wav_bytes(sample_count: usize, bit_depth: u16, channels: u16) -> usize {
+    let header = if channels > 2 || bit_depth > 16 { 68 } else { 44 };
+    header + sample_count * channels as usize * (bit_depth as usize / 8)
+}
+
+/// Read a PCM (8-32 bit integer) or 32-bit float WAV file, scaled to [-1, 1]
+pub fn read_wav_file<P: AsRef<Path>>(filename: P) -> Result<AudioBuffer> {
+    let path = filename.as_ref();
+    let reader = hound::WavReader::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let spec = reader.spec();
+    let samples = match spec.sample_format {
+        SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+        SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .into_samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+    Ok(AudioBuffer { samples, sample_rate: spec.sample_rate, channels: spec.channels })
+}
+
+/// Write `audio` as a 16-bit WAV file
+pub fn write_wav_file<P: AsRef<Path>>(filename: P, audio: &AudioBuffer) -> Result<()> {
+    let file = BufWriter::new(File::create(filename)?);
+    write_pcm(file, &audio.samples, audio.sample_rate, audio.channels, 16, PcmOptions::default())
+}
+
+/// Write a 16-bit WAV file with explicit quantization settings
+pub fn write_wav_file_with_options<P: AsRef<Path>>(
+    filename: P,
+    audio_data: &[f32],
+    sample_rate: i32,
+    options: PcmOptions,
+) -> Result<()> {
+    let file = BufWriter::new(File::create(filename)?);
+    write_wav_with_options(file, audio_data, sample_rate, 16, options)
+}
+
+/// Broadcast Wave Format `bext` chunk fields (EBU Tech 3285, version 1)
+///
+/// Text fields are ASCII; non-ASCII characters are replaced with `?` and each field is
+/// cut to its fixed width (description 256, originator and reference 32 bytes).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BextInfo {
+    pub description: String,
+    pub originator: String,
+    pub originator_reference: String,
+    /// `yyyy-mm-dd`
+    pub origination_date: String,
+    /// `hh:mm:ss`
+    pub origination_time: String,
+    /// Position of the first sample, in samples since midnight
+    pub time_reference: u64,
+}
+
+impl BextInfo {
+    /// Set the origination date and time (UTC) from seconds since the Unix epoch
+    pub fn with_timestamp(mut self, unix_secs: u64) -> Self {
+        // Civil date from a day count (Howard Hinnant's algorithm)
+        let days = (unix_secs / 86_400) as i64 + 719_468;
+        let era = days.div_euclid(146_097);
+        let doe = days.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + i64::from(month <= 2);
+
+        let secs = unix_secs % 86_400;
+        self.origination_date = format!("{:04}-{:02}-{:02}", year, month, day);
+        self.origination_time = format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60);
+        self
+    }
+
+    /// Chunk payload: the fixed 602-byte version 1 layout with an empty coding history
+    fn payload(&self) -> Vec<u8> {
+        fn field(out: &mut Vec<u8>, text: &str, width: usize) {
+            let start = out.len();
+            out.extend(text.chars().map(|c| if c.is_ascii() { c as u8 } else { b'?' }).take(width));
+            out.resize(start + width, 0);
+        }
+
+        let mut out = Vec::with_capacity(602);
+        field(&mut out, &self.description, 256);
+        field(&mut out, &self.originator, 32);
+        field(&mut out, &self.originator_reference, 32);
+        field(&mut out, &self.origination_date, 10);
+        field(&mut out, &self.origination_time, 8);
+        out.extend_from_slice(&self.time_reference.to_le_bytes());
+        out.extend_from_slice(&1u16.to_le_bytes());
+        // UMID (64 bytes) and reserved space (190 bytes), all zero
+        out.resize(out.len() + 64 + 190, 0);
+        out
+    }
+}
+
+/// Insert a `bext` chunk right after the RIFF/WAVE header of an in-memory WAV file
+pub fn insert_bext_chunk(wav: &mut Vec<u8>, info: &BextInfo) -> Result<()> {
+    if wav.len() < 12 || &wav[0..4] != b"RIFF" || &wav[8..12] != b"WAVE" {
+        bail!("Not a RIFF/WAVE file");
+    }
+    let payload = info.payload();
+    let mut chunk = Vec::with_capacity(8 + payload.len());
+    chunk.extend_from_slice(b"bext");
+    chunk.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    chunk.extend_from_slice(&payload);
+
+    let riff_size = u32::from_le_bytes(wav[4..8].try_into()?)
+        .checked_add(chunk.len() as u32)
+        .context("WAV file too large for a bext chunk")?;
+    wav[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    wav.splice(12..12, chunk);
+    Ok(())
+}
+
+/// Write a 16-bit Broadcast Wave file: a WAV carrying a `bext` chunk
+pub fn write_bwf_file<P: AsRef<Path>>(
+    filename: P,
+    audio_data: &[f32],
+    sample_rate: i32,
+    options: PcmOptions,
+    info: &BextInfo,
+) -> Result<()> {
+    let mut cursor = Cursor::new(Vec::new());
+    write_wav_with_options(&mut cursor, audio_data, sample_rate, 16, options)?;
+    let mut bytes = cursor.into_inner();
+    insert_bext_chunk(&mut bytes, info)?;
+    std::fs::write(filename, bytes)?;
+    Ok(())
+}
+
+/// Incremental mono WAV writer that accepts audio chunk by chunk
+///
+/// Only the current chunk is held in memory. The header is completed by `finish`,
+/// or on drop (where errors are ignored).
+pub struct WavSink<W: Write + Seek> {
+    writer: Option<WavWriter<W>>,
+    bit_depth: u16,
+    options: PcmOptions,
+    samples_written: u64,
+}
+
+impl WavSink<BufWriter<File>> {
+    /// Create a 16-bit WAV file at `filename`
+    pub fn create<P: AsRef<Path>>(filename: P, sample_rate: i32, options: PcmOptions) -> Result<Self> {
+        let file = BufWriter::new(File::create(filename)?);
+        WavSink::new(file, sample_rate, 16, options)
+    }
+}
+
+impl<W: Write + Seek> WavSink<W> {
+    pub fn new(writer: W, sample_rate: i32, bit_depth: u16, options: PcmOptions) -> Result<Self> {
+        if !matches!(bit_depth, 16 | 24 | 32) {
+            bail!("Unsupported bit depth: {} (expected 16, 24 or 32)", bit_depth);
+        }
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: sample_rate as u32,
+            bits_per_sample: bit_depth,
+            sample_format: SampleFormat::Int,
+        };
+        Ok(WavSink {
+            writer: Some(WavWriter::new(writer, spec)?),
+            bit_depth,
+            options,
+            samples_written: 0,
+        })
+    }
+
+    /// Append samples to the file
+    pub fn write_chunk(&mut self, audio: &[f32]) -> Result<()> {
+        let writer = self.writer.as_mut().context("WAV sink is already finished")?;
+        for &sample in audio {
+            writer.write_sample(quantize(sample, self.bit_depth, self.options))?;
+        }
+        self.samples_written += audio.len() as u64;
+        Ok(())
+    }
+
+    pub fn samples_written(&self) -> u64 {
+        self.samples_written
+    }
+
+    /// Complete the header and flush, reporting any error
+    pub fn finish(mut self) -> Result<()> {
+        if let Some(writer) = self.writer.take() {
+            writer.finalize()?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek> Drop for WavSink<W> {
+    fn drop(&mut self) {
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.finalize();
+        }
+    }
+}
+
+/// Write each track to its own channel of one 16-bit WAV file
+///
+/// Track `i` becomes channel `i` (channel 0 first in each interleaved frame). Shorter
+/// tracks are padded with silence to the longest one.
+pub fn write_multichannel_wav<P: AsRef<Path>>(
+    filename: P,
+    tracks: &[Vec<f32>],
+    sample_rate: i32,
+    options: PcmOptions,
+) -> Result<()> {
+    if tracks.is_empty() || tracks.len() > u16::MAX as usize {
+        bail!("Expected between 1 and {} tracks, got {}", u16::MAX, tracks.len());
+    }
+
+    let spec = WavSpec {
+        channels: tracks.len() as u16,
+        sample_rate: sample_rate as u32,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    };
+
+    let frames = tracks.iter().map(|t| t.len()).max().unwrap_or(0);
+    let mut writer = WavWriter::new(BufWriter::new(File::create(filename)?), spec)?;
+    for frame in 0..frames {
+        for track in tracks {
+            let sample = track.get(frame).copied().unwrap_or(0.0);
+            writer.write_sample(quantize(sample, 16, options) as i16)?;
+        }
+    }
+
+    writer.finalize()?;
+    Ok(())
+}
+
+/// Byte order for raw sample dumps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Endian {
+    /// Native order on x86 and most ARM platforms
+    #[default]
+    Little,
+    Big,
+}
+
+/// Write headerless 32-bit float PCM samples in the given byte order
+pub fn write_raw_pcm<P: AsRef<Path>>(filename: P, audio_data: &[f32], endian: Endian) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    for &sample in audio_data {
+        let bytes = match endian {
+            Endian::Little => sample.to_le_bytes(),
+            Endian::Big => sample.to_be_bytes(),
+        };
+        writer.write_all(&bytes)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Encode mono audio as Opus in an Ogg container at `bitrate` bits per second
+///
+/// Opus runs at 48 kHz natively, so the audio is resampled (linear) to 48 kHz
+/// first; the original rate is recorded in the header for players that care.
+#[cfg(feature = "opus")]
+pub fn write_opus_file<P: AsRef<Path>>(
+    filename: P,
+    audio_data: &[f32],
+    sample_rate: i32,
+    bitrate: i32,
+) -> Result<()> {
+    use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+
+    const OPUS_RATE: u32 = 48_000;
+    const FRAME_SIZE: usize = 960; // 20 ms at 48 kHz
+    const SERIAL: u32 = 0x5375_7072;
+
+    let audio = resample_linear(audio_data, sample_rate as u32, OPUS_RATE);
+
+    let mut encoder = opus::Encoder::new(OPUS_RATE, opus::Channels::Mono, opus::Application::Voip)?;
+    encoder.set_bitrate(opus::Bitrate::Bits(bitrate))?;
+    let pre_skip = encoder.get_lookahead()? as usize;
+
+    let mut writer = PacketWriter::new(BufWriter::new(File::create(filename)?));
+
+    // OpusHead identification header (RFC 7845, section 5.1)
+    let mut head = Vec::with_capacity(19);
+    head.extend_from_slice(b"OpusHead");
+    head.push(1); // version
+    head.push(1); // channel count
+    head.extend_from_slice(&(pre_skip as u16).to_le_bytes());
+    head.extend_from_slice(&(sample_rate as u32).to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+    head.push(0); // channel mapping family
+    writer.write_packet(head, SERIAL, PacketWriteEndInfo::EndPage, 0)?;
+
+    // OpusTags comment header (section 5.2)
+    let vendor = concat!("supertonic-tts ", env!("CARGO_PKG_VERSION"));
+    let mut tags = Vec::new();
+    tags.extend_from_slice(b"OpusTags");
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor.as_bytes());
+    tags.extend_from_slice(&0u32.to_le_bytes()); // no user comments
+    writer.write_packet(tags, SERIAL, PacketWriteEndInfo::EndPage, 0)?;
+
+    // Pad so the decoder still yields every sample after dropping pre_skip
+    let total = audio.len() + pre_skip;
+    let n_frames = total.div_ceil(FRAME_SIZE).max(1);
+    let mut padded = audio;
+    padded.resize(n_frames * FRAME_SIZE, 0.0);
+
+    let mut packet = vec![0u8; 4000];
+    for (i, frame) in padded.chunks(FRAME_SIZE).enumerate() {
+        let len = encoder.encode_float(frame, &mut packet)?;
+        let last = i + 1 == n_frames;
+        let granule = if last { total } else { (i + 1) * FRAME_SIZE };
+        let end_info = if last {
+            PacketWriteEndInfo::EndStream
+        } else {
+            PacketWriteEndInfo::NormalPacket
+        };
+        writer.write_packet(packet[..len].to_vec(), SERIAL, end_info, granule as u64)?;
+    }
+
+    Ok(())
+}
+
+/// Resample audio with linear interpolation (adequate for speech, not for mastering)
+pub fn resample_linear(audio: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || audio.is_empty() {
+        return audio.to_vec();
+    }
+
+    let out_len = (audio.len() as u64 * to_rate as u64 / from_rate as u64) as usize;
+    let step = from_rate as f64 / to_rate as f64;
+    (0..out_len)
+        .map(|i| {
+            let pos = i as f64 * step;
+            let idx = pos as usize;
+            let frac = (pos - idx as f64) as f32;
+            let a = audio[idx.min(audio.len() - 1)];
+            let b = audio[(idx + 1).min(audio.len() - 1)];
+            a + (b - a) * frac
+        })
+        .collect()
+}
+
+// ============================================================================
+// Audio Post-processing
+// ============================================================================
+
+/// Second-order IIR filter (RBJ Audio EQ Cookbook coefficients, transposed direct form II)
+#[derive(Debug, Clone, Copy)]
+pub struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    fn from_coeffs(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        Biquad {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    /// High-pass filter with cutoff `cutoff_hz` and quality factor `q`
+    pub fn highpass(sample_rate: i32, cutoff_hz: f32, q: f32) -> Self {
+        let w0 = 2.0 * std::f32::consts::PI * cutoff_hz / sample_rate as f32;
+        let (sin_w0, cos_w0) = w0.sin_cos();
+        let alpha = sin_w0 / (2.0 * q);
+        Biquad::from_coeffs(
+            (1.0 + cos_w0) / 2.0,
+            -(1.0 + cos_w0),
+            (1.0 + cos_w0) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos_w0,
+            1.0 - alpha,
+        )
+    }
+
+    /// Peaking EQ boosting (or cutting) `gain_db` around `freq_hz` with quality factor `q`
+    pub fn peaking(sample_rate: i32, freq_hz: f32, gain_db: f32, q: f32) -> Self {
+        let a = 10f32.powf(gain_db / 40.0);
+        let w0 = 2.0 * std::f32::consts::PI * freq_hz / sample_rate as f32;
+        let (sin_w0, cos_w0) = w0.sin_cos();
+        let alpha = sin_w0 / (2.0 * q);
+        Biquad::from_coeffs(
+            1.0 + alpha * a,
+            -2.0 * cos_w0,
+            1.0 - alpha * a,
+            1.0 + alpha / a,
+            -2.0 * cos_w0,
+            1.0 - alpha / a,
+        )
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+
+    pub fn process_buffer(&mut self, audio: &mut [f32]) {
+        for sample in audio.iter_mut() {
+            *sample = self.process(*sample);
+        }
+    }
+}
+
+/// Remove low-frequency rumble below `cutoff_hz` in place
+///
+/// A single 2nd-order Butterworth section (Q = 0.707): a gentle 12 dB/octave roll-off.
+pub fn highpass(audio: &mut [f32], sample_rate: i32, cutoff_hz: f32) {
+    Biquad::highpass(sample_rate, cutoff_hz, std::f32::consts::FRAC_1_SQRT_2).process_buffer(audio);
+}
+
+/// One peaking band of an `Equalizer`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BandSpec {
+    pub freq_hz: f32,
+    pub gain_db: f32,
+    pub q: f32,
+}
+
+/// Multi-band EQ: peaking biquads applied in series
+#[derive(Debug, Clone, Default)]
+pub struct Equalizer {
+    bands: Vec<BandSpec>,
+}
+
+impl Equalizer {
+    pub fn new(bands: Vec<BandSpec>) -> Self {
+        Equalizer { bands }
+    }
+
+    pub fn bands(&self) -> &[BandSpec] {
+        &self.bands
+    }
+
+    /// Filter `audio` in place through every band
+    ///
+    /// Bands at or above Nyquist are skipped, as their coefficients would be meaningless.
+    pub fn apply(&self, audio: &mut [f32], sample_rate: i32) {
+        let nyquist = sample_rate as f32 / 2.0;
+        for band in self.bands.iter().filter(|b| b.freq_hz > 0.0 && b.freq_hz < nyquist && b.q > 0.0) {
+            Biquad::peaking(sample_rate, band.freq_hz, band.gain_db, band.q).process_buffer(audio);
+        }
+    }
+}
+
+/// Look-ahead of `limit_peaks` in samples (about 5.8 ms at 44.1 kHz)
+pub const LIMIT_LOOKAHEAD: usize = 256;
+
+/// Release of `limit_peaks` in samples, after which the gain is back to unity
+const LIMIT_RELEASE: usize = 4 * LIMIT_LOOKAHEAD;
+
+/// Brickwall limiter: keep every sample within `ceiling` (linear amplitude) in place
+///
+/// Only the neighbourhood of each overshoot is attenuated. Before a peak the gain falls by at
+/// most `1 / LIMIT_LOOKAHEAD` per sample, so it reaches exactly `ceiling / |peak|` at the peak,
+/// and after it recovers by at most `1 / LIMIT_RELEASE` per sample; a peak thus affects at
+/// most `LIMIT_LOOKAHEAD` samples before it and `LIMIT_RELEASE` after. Audio already within
+/// the ceiling is left untouched. Runs in linear time however many samples overshoot.
+pub fn limit_peaks(audio: &mut [f32], ceiling: f32) {
+    if ceiling <= 0.0 {
+        return;
+    }
+    // Gain each sample needs on its own, then slope-limited in both directions: the backward
+    // pass builds the attack ramps, the forward pass the release ramps
+    let mut gain: Vec<f32> = audio
+        .iter()
+        .map(|&x| if x.abs() > ceiling { ceiling / x.abs() } else { 1.0 })
+        .collect();
+    let attack = 1.0 / LIMIT_LOOKAHEAD as f32;
+    for i in (0..gain.len().saturating_sub(1)).rev() {
+        gain[i] = gain[i].min(gain[i + 1] + attack);
+    }
+    let release = 1.0 / LIMIT_RELEASE as f32;
+    for i in 1..gain.len() {
+        gain[i] = gain[i].min(gain[i - 1] + release);
+    }
+    for (x, g) in audio.iter_mut().zip(gain) {
+        *x *= g;
+    }
+}
+
+/// Scale `audio` by a linear `gain`, then limit at full scale so a boost cannot clip
+pub fn apply_gain(audio: &mut [f32], gain: f32) {
+    audio.iter_mut().for_each(|x| *x *= gain);
+    limit_peaks(audio, 1.0);
+}
+
+/// Feed-forward compressor: above `threshold_db` (dBFS), level rises only 1/`ratio` as fast
+///
+/// The gain reduction follows each sample's level with separate attack and release time
+/// constants, smoothed in dB. No makeup gain is applied, so the result is never louder.
+pub fn compress(audio: &mut [f32], threshold_db: f32, ratio: f32, attack_ms: f32, release_ms: f32, sample_rate: i32) {
+    let coeff = |ms: f32| if ms <= 0.0 { 0.0 } else { (-1000.0 / (ms * sample_rate as f32)).exp() };
+    let (attack, release) = (coeff(attack_ms), coeff(release_ms));
+    let slope = 1.0 - 1.0 / ratio.max(1.0);
+    let mut reduction_db = 0.0f32;
+    for x in audio.iter_mut() {
+        let level_db = 20.0 * x.abs().max(1e-10).log10();
+        let target_db = (level_db - threshold_db).max(0.0) * slope;
+        let c = if target_db > reduction_db { attack } else { release };
+        reduction_db = c * reduction_db + (1.0 - c) * target_db;
+        *x *= 10f32.powf(-reduction_db / 20.0);
+    }
+}
+
+/// Which level `match_level` equalizes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LevelMeasure {
+    /// Root mean square over the whole signal, the closer match to perceived loudness
+    #[default]
+    Rms,
+    /// Largest absolute sample value
+    Peak,
+}
+
+/// Scale `audio` so its `measure` equals `target` (linear), limiting at full scale as
+/// `apply_gain` does; silent audio is left alone
+pub fn match_level(audio: &mut [f32], target: f32, measure: LevelMeasure) {
+    let stats = audio_stats(audio);
+    let current = match measure {
+        LevelMeasure::Rms => stats.rms,
+        LevelMeasure::Peak => stats.peak,
+    };
+    if current > 0.0 {
+        apply_gain(audio, target / current);
+    }
+}
+
+/// Level statistics of a signal
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct AudioStats {
+    /// Largest absolute sample value
+    pub peak: f32,
+    pub rms: f32,
+    /// Peak and RMS in dB relative to full scale (1.0), floored at -200 dB for silence
+    pub peak_dbfs: f32,
+    pub rms_dbfs: f32,
+    /// Samples at or beyond full scale, which clip when written as integers
+    pub clipped_samples: usize,
+}
+
+/// Compute peak, RMS, their dBFS values and the number of clipping samples
+pub fn audio_stats(audio: &[f32]) -> AudioStats {
+    let peak = audio.iter().fold(0.0f32, |a, &b| a.max(b.abs()));
+    let rms = if audio.is_empty() {
+        0.0
+    } else {
+        (audio.iter().map(|&x| x as f64 * x as f64).sum::<f64>() / audio.len() as f64).sqrt() as f32
+    };
+    let dbfs = |level: f32| 20.0 * level.max(1e-10).log10();
+
+    AudioStats {
+        peak,
+        rms,
+        peak_dbfs: dbfs(peak),
+        rms_dbfs: dbfs(rms),
+        clipped_samples: audio.iter().filter(|x| x.abs() >= 1.0).count(),
+    }
+}
+
+/// Count the leading and trailing samples whose magnitude is at or below `threshold`
+///
+/// The audio is not modified. When no sample exceeds the threshold, both counts are the
+/// full length.
+pub fn silence_bounds(audio: &[f32], threshold: f32) -> (usize, usize) {
+    match audio.iter().position(|x| x.abs() > threshold) {
+        Some(first) => {
+            let last = audio.iter().rposition(|x| x.abs() > threshold).unwrap_or(first);
+            (first, audio.len() - 1 - last)
+        }
+        None => (audio.len(), audio.len()),
+    }
+}
+
+/// Surround audio with `start` and `end` samples of silence
+pub fn pad_silence(audio: &[f32], start: usize, end: usize) -> Vec<f32> {
+    let mut padded = vec![0.0f32; start + audio.len() + end];
+    padded[start..start + audio.len()].copy_from_slice(audio);
+    padded
+}
+
+/// Sum tracks into one mono signal, each scaled by its gain and delayed by its offset (in samples)
+///
+/// If the sum peaks above full scale, the whole mix is scaled down to avoid clipping.
+pub fn mix_tracks(tracks: &[&[f32]], gains: &[f32], offsets: &[usize]) -> Vec<f32> {
+    let len = tracks
+        .iter()
+        .zip(offsets)
+        .map(|(t, &o)| o + t.len())
+        .max()
+        .unwrap_or(0);
+
+    let mut mix = vec![0.0f32; len];
+    for ((track, &gain), &offset) in tracks.iter().zip(gains).zip(offsets) {
+        for (j, &sample) in track.iter().enumerate() {
+            mix[offset + j] += sample * gain;
+        }
+    }
+
+    let peak = mix.iter().fold(0.0f32, |a, &b| a.max(b.abs()));
+    if peak > 1.0 {
+        for sample in mix.iter_mut() {
+            *sample /= peak;
+        }
+    }
+    mix
+}
+
+/// Crossfade `a` into `b` over `fade_len` samples with an equal-power curve
+///
+/// The last `fade_len` samples of `a` overlap the first `fade_len` samples of `b`,
+/// so the result is `a.len() + b.len() - fade_len` samples long.
+pub fn crossfade(a: &[f32], b: &[f32], fade_len: usize) -> Vec<f32> {
+    let fade_len = fade_len.min(a.len()).min(b.len());
+    let split = a.len() - fade_len;
+
+    let mut out = Vec::with_capacity(a.len() + b.len() - fade_len);
+    out.extend_from_slice(&a[..split]);
+    for i in 0..fade_len {
+        let t = (i as f32 + 0.5) / fade_len as f32;
+        let gain_out = (t * std::f32::consts::FRAC_PI_2).cos();
+        let gain_in = (t * std::f32::consts::FRAC_PI_2).sin();
+        out.push(a[split + i] * gain_out + b[i] * gain_in);
+    }
+    out.extend_from_slice(&b[fade_len..]);
+    out
+}
+
+/// Fade in the first `fade_len` samples with a raised-cosine curve to suppress an onset pop
+///
+/// Unlike `crossfade`, this works in place on a single signal and only touches its start.
+pub fn onset_fade(audio: &mut [f32], fade_len: usize) {
+    let fade_len = fade_len.min(audio.len());
+    for (i, sample) in audio.iter_mut().take(fade_len).enumerate() {
+        let t = i as f32 / fade_len as f32;
+        *sample *= 0.5 - 0.5 * (std::f32::consts::PI * t).cos();
+    }
+}
+
+/// Build `repeats` seamless copies of `audio`
+///
+/// The tail of each period is crossfaded into the head, so the period is
+/// `fade_len` samples shorter than the input and loops without a click.
+pub fn make_loop(audio: &[f32], repeats: usize, fade_len: usize) -> Vec<f32> {
+    let fade_len = fade_len.min(audio.len() / 2);
+    let len = audio.len();
+    let period = crossfade(&audio[fade_len..], &audio[..fade_len], fade_len);
+    debug_assert_eq!(period.len(), len - fade_len);
+    period.repeat(repeats)
+}
+
+/// Compute an STFT magnitude spectrogram in dB, one `n_fft / 2 + 1` bin row per frame
+///
+/// Uses a Hann window; frames are zero-padded past the end of the audio.
+pub fn stft_magnitude_db(audio: &[f32], n_fft: usize, hop: usize) -> Vec<Vec<f32>> {
+    let mut planner = rustfft::FftPlanner::<f32>::new();
+    let fft = planner.plan_fft_forward(n_fft);
+    let window: Vec<f32> = (0..n_fft)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / n_fft as f32).cos())
+        .collect();
+
+    let n_frames = audio.len().div_ceil(hop).max(1);
+    (0..n_frames)
+        .map(|f| {
+            let start = f * hop;
+            let mut buf: Vec<rustfft::num_complex::Complex<f32>> = (0..n_fft)
+                .map(|i| {
+                    let sample = audio.get(start + i).copied().unwrap_or(0.0);
+                    rustfft::num_complex::Complex::new(sample * window[i], 0.0)
+                })
+                .collect();
+            fft.process(&mut buf);
+            buf[..n_fft / 2 + 1]
+                .iter()
+                .map(|c| 20.0 * (c.norm() + 1e-10).log10())
+                .collect()
+        })
+        .collect()
+}
+
+/// Map a value in [0, 1] to a black-purple-orange-yellow heat colour
+#[cfg(feature = "spectrogram")]
+fn heat_colormap(v: f32) -> [u8; 3] {
+    const STOPS: [[f32; 3]; 5] = [
+        [0.0, 0.0, 0.0],
+        [80.0, 18.0, 123.0],
+        [182.0, 54.0, 121.0],
+        [251.0, 136.0, 97.0],
+        [252.0, 253.0, 191.0],
+    ];
+    let v = v.clamp(0.0, 1.0) * (STOPS.len() - 1) as f32;
+    let i = (v as usize).min(STOPS.len() - 2);
+    let t = v - i as f32;
+    let mut rgb = [0u8; 3];
+    for c in 0..3 {
+        rgb[c] = (STOPS[i][c] + (STOPS[i + 1][c] - STOPS[i][c]) * t) as u8;
+    }
+    rgb
+}
+
+/// Write a spectrogram PNG (time left to right, low frequencies at the bottom)
+///
+/// Magnitudes are shown over an 80 dB range below the loudest bin.
+#[cfg(feature = "spectrogram")]
+pub fn write_spectrogram_png<P: AsRef<Path>>(filename: P, audio_data: &[f32]) -> Result<()> {
+    const N_FFT: usize = 1024;
+    const HOP: usize = 256;
+    const DYNAMIC_RANGE_DB: f32 = 80.0;
+
+    let frames = stft_magnitude_db(audio_data, N_FFT, HOP);
+    let n_bins = N_FFT / 2 + 1;
+    let max_db = frames
+        .iter()
+        .flatten()
+        .fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+
+    let img = image::RgbImage::from_fn(frames.len() as u32, n_bins as u32, |x, y| {
+        let db = frames[x as usize][n_bins - 1 - y as usize];
+        let v = (db - (max_db - DYNAMIC_RANGE_DB)) / DYNAMIC_RANGE_DB;
+        image::Rgb(heat_colormap(v))
+    });
+    img.save(filename.as_ref())
+        .with_context(|| format!("Failed to write spectrogram {}", filename.as_ref().display()))?;
+    Ok(())
+}
+
+// ============================================================================
+// Text Chunking
+// ============================================================================
+
+const MAX_CHUNK_LENGTH: usize = 300;
+
+const ABBREVIATIONS: &[&str] = &[
+    "Dr.", "Mr.", "Mrs.", "Ms.", "Prof.", "Sr.", "Jr.",
+    "St.", "Ave.", "Rd.", "Blvd.", "Dept.", "Inc.", "Ltd.",
+    "Co.", "Corp.", "etc.", "vs.", "i.e.", "e.g.", "Ph.D.",
+];
+
+pub fn chunk_text(text: &str, max_len: Option<usize>) -> Vec<String> {
     let max_len = max_len.unwrap_or(MAX_CHUNK_LENGTH);
     let text = text.trim();
     
@@ -335,350 +2049,1745 @@ pub fn chunk_text(text: &str, max_len: Option<usize>) -> Vec<String> {
         return vec![String::new()];
     }
 
-    // Split by paragraphs
-    let para_re = Regex::new(r"\n\s*\n").unwrap();
-    let paragraphs: Vec<&str> = para_re.split(text).collect();
-    let mut chunks = Vec::new();
+    // Split by paragraphs
+    let para_re = Regex::new(r"\n\s*\n").unwrap();
+    let paragraphs: Vec<&str> = para_re.split(text).collect();
+    let mut chunks = Vec::new();
+
+    for para in paragraphs {
+        let para = para.trim();
+        if para.is_empty() {
+            continue;
+        }
+
+        if para.len() <= max_len {
+            chunks.push(para.to_string());
+            continue;
+        }
+
+        // Split by sentences
+        let sentences = split_sentences(para);
+        let mut current = String::new();
+        let mut current_len = 0;
+
+        for sentence in sentences {
+            let sentence = sentence.trim();
+            if sentence.is_empty() {
+                continue;
+            }
+
+            let sentence_len = sentence.len();
+            if sentence_len > max_len {
+                // If sentence is longer than max_len, split by comma or space
+                if !current.is_empty() {
+                    chunks.push(current.trim().to_string());
+                    current.clear();
+                    current_len = 0;
+                }
+
+                // Try splitting by comma
+                let parts: Vec<&str> = sentence.split(',').collect();
+                for part in parts {
+                    let part = part.trim();
+                    if part.is_empty() {
+                        continue;
+                    }
+
+                    let part_len = part.len();
+                    if part_len > max_len {
+                        // Split by space as last resort
+                        let words: Vec<&str> = part.split_whitespace().collect();
+                        let mut word_chunk = String::new();
+                        let mut word_chunk_len = 0;
+
+                        for word in words {
+                            let word_len = word.len();
+                            if word_chunk_len + word_len + 1 > max_len && !word_chunk.is_empty() {
+                                chunks.push(word_chunk.trim().to_string());
+                                word_chunk.clear();
+                                word_chunk_len = 0;
+                            }
+
+                            if !word_chunk.is_empty() {
+                                word_chunk.push(' ');
+                                word_chunk_len += 1;
+                            }
+                            word_chunk.push_str(word);
+                            word_chunk_len += word_len;
+                        }
+
+                        if !word_chunk.is_empty() {
+                            chunks.push(word_chunk.trim().to_string());
+                        }
+                    } else {
+                        if current_len + part_len + 1 > max_len && !current.is_empty() {
+                            chunks.push(current.trim().to_string());
+                            current.clear();
+                            current_len = 0;
+                        }
+
+                        if !current.is_empty() {
+                            current.push_str(", ");
+                            current_len += 2;
+                        }
+                        current.push_str(part);
+                        current_len += part_len;
+                    }
+                }
+                continue;
+            }
+
+            if current_len + sentence_len + 1 > max_len && !current.is_empty() {
+                chunks.push(current.trim().to_string());
+                current.clear();
+                current_len = 0;
+            }
+
+            if !current.is_empty() {
+                current.push(' ');
+                current_len += 1;
+            }
+            current.push_str(sentence);
+            current_len += sentence_len;
+        }
+
+        if !current.is_empty() {
+            chunks.push(current.trim().to_string());
+        }
+    }
+
+    if chunks.is_empty() {
+        vec![String::new()]
+    } else {
+        chunks
+    }
+}
+
+/// Split like `chunk_text`, flagging chunks that end a paragraph
+pub fn chunk_text_with_breaks(text: &str, max_len: Option<usize>) -> Vec<(String, bool)> {
+    let para_re = Regex::new(r"\n\s*\n").unwrap();
+    let mut chunks = Vec::new();
+    for para in para_re.split(text.trim()).filter(|p| !p.trim().is_empty()) {
+        let para_chunks = chunk_text(para, max_len);
+        let last = para_chunks.len() - 1;
+        chunks.extend(para_chunks.into_iter().enumerate().map(|(i, c)| (c, i == last)));
+    }
+
+    if chunks.is_empty() {
+        vec![(String::new(), true)]
+    } else {
+        chunks
+    }
+}
+
+/// Silence (in seconds) inserted after a chunk of long text, chosen by how the chunk ends
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChunkPauses {
+    /// After a chunk ending in `.`, `!` or `?`
+    pub period: f32,
+    /// After a chunk split mid-sentence (at a comma or between words)
+    pub comma: f32,
+    /// After the last chunk of a paragraph
+    pub newline: f32,
+}
+
+impl ChunkPauses {
+    /// The same pause after every chunk
+    pub fn uniform(seconds: f32) -> Self {
+        ChunkPauses {
+            period: seconds,
+            comma: seconds,
+            newline: seconds,
+        }
+    }
+
+    /// Pause to insert after `chunk`
+    pub fn after(&self, chunk: &str, paragraph_end: bool) -> f32 {
+        if paragraph_end {
+            self.newline
+        } else if chunk.trim_end().ends_with(['.', '!', '?']) {
+            self.period
+        } else {
+            self.comma
+        }
+    }
+}
+
+fn split_sentences(text: &str) -> Vec<String> {
+    // Rust's regex doesn't support lookbehind, so we use a simpler approach
+    // Split on sentence boundaries and then check if they're abbreviations
+    let re = Regex::new(r"([.!?])\s+").unwrap();
+    
+    // Find all matches
+    let matches: Vec<_> = re.find_iter(text).collect();
+    if matches.is_empty() {
+        return vec![text.to_string()];
+    }
+    
+    let mut sentences = Vec::new();
+    let mut last_end = 0;
+    
+    for m in matches {
+        // Get the text before the punctuation
+        let before_punc = &text[last_end..m.start()];
+        
+        // Check if this ends with an abbreviation
+        let mut is_abbrev = false;
+        for abbrev in ABBREVIATIONS {
+            let combined = format!("{}{}", before_punc.trim(), &text[m.start()..m.start()+1]);
+            if combined.ends_with(abbrev) {
+                is_abbrev = true;
+                break;
+            }
+        }
+        
+        if !is_abbrev {
+            // This is a real sentence boundary
+            sentences.push(text[last_end..m.end()].to_string());
+            last_end = m.end();
+        }
+    }
+    
+    // Add the remaining text
+    if last_end < text.len() {
+        sentences.push(text[last_end..].to_string());
+    }
+    
+    if sentences.is_empty() {
+        vec![text.to_string()]
+    } else {
+        sentences
+    }
+}
+
+// ============================================================================
+// Utility Functions
+// ============================================================================
+
+pub fn timer<F, T>(name: &str, f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T>,
+{
+    let start = Instant::now();
+    println!("{}...", name);
+    let result = f()?;
+    let elapsed = start.elapsed().as_secs_f64();
+    println!("  -> {} completed in {:.2} sec", name, elapsed);
+    Ok(result)
+}
+
+pub fn sanitize_filename(text: &str, max_len: usize) -> String {
+    // Take first max_len characters (Unicode code points, not bytes)
+    text.chars()
+        .take(max_len)
+        .map(|c| {
+            // is_alphanumeric() works with all Unicode letters and digits
+            if c.is_alphanumeric() {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Render an output name template, replacing each `{key}` with its value
+///
+/// Unknown placeholders are left untouched.
+pub fn render_name_template(template: &str, fields: &[(&str, String)]) -> String {
+    let mut name = template.to_string();
+    for (key, value) in fields {
+        name = name.replace(&format!("{{{}}}", key), value);
+    }
+    name
+}
+
+/// Standard (RFC 4648) base64 with `=` padding
+pub fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Stable 64-bit FNV-1a hash of `parts` as 16 hex digits, for content-addressed names
+///
+/// Parts are separated by a 0x1f byte so `["ab", "c"]` and `["a", "bc"]` differ.
+pub fn content_hash(parts: &[&str]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for (i, part) in parts.iter().enumerate() {
+        let sep: &[u8] = if i > 0 { &[0x1f] } else { &[] };
+        for &byte in sep.iter().chain(part.as_bytes()) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+// ============================================================================
+// Metrics
+// ============================================================================
+
+/// Receives per-stage timings and the real-time factor from `TextToSpeech`
+///
+/// Both methods default to doing nothing, so sinks implement only what they need.
+pub trait MetricsSink: Send {
+    /// Called once per inference for each stage, e.g. `encode`, `denoise`, `vocoder`, `total`
+    fn record_stage(&mut self, _name: &str, _duration: Duration) {}
+
+    /// Called once per inference with processing time divided by audio duration
+    fn record_rtf(&mut self, _rtf: f32) {}
+}
+
+/// Metrics sink that discards everything (the default)
+pub struct NoopMetrics;
+
+impl MetricsSink for NoopMetrics {}
+
+/// Metrics sink that keeps running totals and rewrites a Prometheus text-format file
+///
+/// The file is replaced after every inference, which suits node_exporter's textfile collector.
+pub struct PrometheusFileSink {
+    path: PathBuf,
+    /// Stage name, observation count and total seconds, in first-seen order
+    stages: Vec<(String, u64, f64)>,
+    rtf_last: f32,
+    rtf_count: u64,
+    rtf_sum: f64,
+}
+
+impl PrometheusFileSink {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        PrometheusFileSink {
+            path: path.as_ref().to_path_buf(),
+            stages: Vec::new(),
+            rtf_last: 0.0,
+            rtf_count: 0,
+            rtf_sum: 0.0,
+        }
+    }
+
+    /// Current metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# HELP supertonic_stage_seconds Time spent in each synthesis stage.\n");
+        out.push_str("# TYPE supertonic_stage_seconds summary\n");
+        for (name, count, sum) in &self.stages {
+            out.push_str(&format!("supertonic_stage_seconds_sum{{stage=\"{}\"}} {}\n", name, sum));
+            out.push_str(&format!("supertonic_stage_seconds_count{{stage=\"{}\"}} {}\n", name, count));
+        }
+        out.push_str("# HELP supertonic_rtf Real-time factor of the latest inference.\n");
+        out.push_str("# TYPE supertonic_rtf gauge\n");
+        out.push_str(&format!("supertonic_rtf {}\n", self.rtf_last));
+        out.push_str("# HELP supertonic_rtf_total Real-time factor over all inferences.\n");
+        out.push_str("# TYPE supertonic_rtf_total summary\n");
+        out.push_str(&format!("supertonic_rtf_total_sum {}\n", self.rtf_sum));
+        out.push_str(&format!("supertonic_rtf_total_count {}\n", self.rtf_count));
+        out
+    }
+
+    /// Write the metrics file via a temporary file so scrapers never see a partial write
+    pub fn flush(&self) -> Result<()> {
+        let tmp_path = self.path.with_extension("prom.tmp");
+        std::fs::write(&tmp_path, self.render())
+            .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, &self.path)
+            .with_context(|| format!("Failed to replace {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+impl MetricsSink for PrometheusFileSink {
+    fn record_stage(&mut self, name: &str, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        match self.stages.iter_mut().find(|(n, _, _)| n == name) {
+            Some((_, count, sum)) => {
+                *count += 1;
+                *sum += secs;
+            }
+            None => self.stages.push((name.to_string(), 1, secs)),
+        }
+    }
+
+    fn record_rtf(&mut self, rtf: f32) {
+        self.rtf_last = rtf;
+        self.rtf_count += 1;
+        self.rtf_sum += rtf as f64;
+        // The RTF is recorded last, so the file reflects the complete inference
+        if let Err(e) = self.flush() {
+            eprintln!("Warning: {:#}", e);
+        }
+    }
+}
+
+// ============================================================================
+// ONNX Runtime Integration
+// ============================================================================
+
+use ort::{
+    execution_providers::{CUDAExecutionProvider, CoreMLExecutionProvider, ExecutionProviderDispatch},
+    session::{builder::SessionBuilder, Session},
+    value::{Tensor, Value, ValueType},
+};
+
+#[derive(Debug, Clone)]
+pub struct Style {
+    pub ttl: Array3<f32>,
+    pub dp: Array3<f32>,
+}
+
+impl Style {
+    /// Build a style from flat row-major buffers, validating their sizes
+    pub fn from_slices(
+        ttl: &[f32],
+        ttl_dims: [usize; 3],
+        dp: &[f32],
+        dp_dims: [usize; 3],
+    ) -> Result<Style> {
+        let ttl_size: usize = ttl_dims.iter().product();
+        if ttl.len() != ttl_size {
+            bail!("ttl slice has {} values, but dims {:?} need {}", ttl.len(), ttl_dims, ttl_size);
+        }
+        let dp_size: usize = dp_dims.iter().product();
+        if dp.len() != dp_size {
+            bail!("dp slice has {} values, but dims {:?} need {}", dp.len(), dp_dims, dp_size);
+        }
+        if ttl_dims[0] != dp_dims[0] {
+            bail!("ttl batch size ({}) must match dp batch size ({})", ttl_dims[0], dp_dims[0]);
+        }
+
+        Ok(Style {
+            ttl: Array3::from_shape_vec((ttl_dims[0], ttl_dims[1], ttl_dims[2]), ttl.to_vec())?,
+            dp: Array3::from_shape_vec((dp_dims[0], dp_dims[1], dp_dims[2]), dp.to_vec())?,
+        })
+    }
+
+    /// Scale the style vectors to exaggerate (> 1) or flatten (< 1) voice characteristics
+    ///
+    /// Experimental: the models were not trained on scaled styles, so factors far
+    /// from 1.0 may produce artifacts.
+    pub fn scale(&self, ttl_factor: f32, dp_factor: f32) -> Style {
+        Style {
+            ttl: &self.ttl * ttl_factor,
+            dp: &self.dp * dp_factor,
+        }
+    }
+
+    /// Weighted sum of `styles`, e.g. 0.5 of one voice, 0.3 of another and 0.2 of a third
+    ///
+    /// All styles must have the same dims, and `weights` must hold one weight per style and
+    /// sum to 1 (within 1e-3).
+    pub fn mix(styles: &[Style], weights: &[f32]) -> Result<Style> {
+        let Some(first) = styles.first() else {
+            bail!("Cannot mix an empty list of styles");
+        };
+        if weights.len() != styles.len() {
+            bail!("Got {} mix weights for {} styles", weights.len(), styles.len());
+        }
+        let total: f32 = weights.iter().sum();
+        if (total - 1.0).abs() > 1e-3 {
+            bail!("Mix weights must sum to 1, but {:?} sum to {}", weights, total);
+        }
+        for (i, style) in styles.iter().enumerate().skip(1) {
+            if style.ttl.dim() != first.ttl.dim() || style.dp.dim() != first.dp.dim() {
+                bail!(
+                    "Style {} has dims ttl {:?} / dp {:?}, but style 0 has ttl {:?} / dp {:?}",
+                    i, style.ttl.dim(), style.dp.dim(), first.ttl.dim(), first.dp.dim()
+                );
+            }
+        }
+
+        let mut mixed = Style {
+            ttl: Array3::zeros(first.ttl.dim()),
+            dp: Array3::zeros(first.dp.dim()),
+        };
+        for (style, &weight) in styles.iter().zip(weights) {
+            mixed.ttl.scaled_add(weight, &style.ttl);
+            mixed.dp.scaled_add(weight, &style.dp);
+        }
+        Ok(mixed)
+    }
+
+    /// Extract a single-item style at `index`
+    pub fn select(&self, index: usize) -> Style {
+        self.slice(index..index + 1)
+    }
+
+    /// Extract the styles of batch items in `range`
+    pub fn slice(&self, range: std::ops::Range<usize>) -> Style {
+        Style {
+            ttl: self.ttl.slice(s![range.clone(), .., ..]).to_owned(),
+            dp: self.dp.slice(s![range, .., ..]).to_owned(),
+        }
+    }
+}
+
+/// Approximate tensor memory for one inference, in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryEstimate {
+    pub text_emb_bytes: usize,
+    /// Noisy latent `xt` (one copy; the denoising loop holds a few at a time)
+    pub latent_bytes: usize,
+    pub wav_bytes: usize,
+    pub total_bytes: usize,
+}
+
+/// Stack per-text id rows (already padded to equal length) into a (bsz, len) array
+fn text_ids_to_array(text_ids: &[Vec<i64>]) -> Result<Array2<i64>> {
+    let len = text_ids.first().map_or(0, |row| row.len());
+    let flat: Vec<i64> = text_ids.iter().flatten().copied().collect();
+    Ok(Array2::from_shape_vec((text_ids.len(), len), flat)?)
+}
+
+/// Run the vocoder on a (bsz, latent_dim, latent_len) latent, returning the flat waveform
+fn run_vocoder(vocoder_ort: &mut Session, latent: Array3<f32>) -> Result<Vec<f32>> {
+    let latent_value = Value::from_array(latent)?;
+    let outputs = vocoder_ort.run(ort::inputs!{
+        "latent" => &latent_value
+    })?;
+    let (_, wav_data) = outputs["wav_tts"].try_extract_tensor::<f32>()?;
+    Ok(wav_data.to_vec())
+}
+
+/// One vector estimator pass; `steps` is (current step, total steps)
+fn run_vector_estimator(
+    vector_est_ort: &mut Session,
+    xt: Array3<f32>,
+    text_emb: &Array3<f32>,
+    style_ttl_value: &Tensor<f32>,
+    latent_mask: Array3<f32>,
+    text_mask: &Array3<f32>,
+    (step, total_step): (usize, usize),
+) -> Result<Array3<f32>> {
+    let bsz = xt.dim().0;
+    let xt_value = Value::from_array(xt)?;
+    let text_emb_value = Value::from_array(text_emb.clone())?;
+    let latent_mask_value = Value::from_array(latent_mask)?;
+    let text_mask_value = Value::from_array(text_mask.clone())?;
+    let current_step_value = Value::from_array(Array::from_elem(bsz, step as f32))?;
+    let total_step_value = Value::from_array(Array::from_elem(bsz, total_step as f32))?;
+
+    let outputs = vector_est_ort.run(ort::inputs!{
+        "noisy_latent" => &xt_value,
+        "text_emb" => &text_emb_value,
+        "style_ttl" => style_ttl_value,
+        "latent_mask" => &latent_mask_value,
+        "text_mask" => &text_mask_value,
+        "current_step" => &current_step_value,
+        "total_step" => &total_step_value
+    })?;
+
+    let (denoised_shape, denoised_data) = outputs["denoised_latent"].try_extract_tensor::<f32>()?;
+    Ok(Array3::from_shape_vec(
+        (denoised_shape[0] as usize, denoised_shape[1] as usize, denoised_shape[2] as usize),
+        denoised_data.to_vec()
+    )?)
+}
+
+/// One denoising step over overlapping time tiles of `tile` frames, crossfaded over `overlap`
+#[allow(clippy::too_many_arguments)]
+fn denoise_tiled(
+    vector_est_ort: &mut Session,
+    xt: &Array3<f32>,
+    text_emb: &Array3<f32>,
+    style_ttl_value: &Tensor<f32>,
+    latent_mask: &Array3<f32>,
+    text_mask: &Array3<f32>,
+    steps: (usize, usize),
+    tile: usize,
+    overlap: usize,
+) -> Result<Array3<f32>> {
+    let len = xt.dim().2;
+    let mut sum = Array3::<f32>::zeros(xt.dim());
+    let mut weight_sum = vec![0.0f32; len];
+    let mut start = 0;
+    loop {
+        // The last tile is shifted back to end exactly at the latent's end
+        let end = (start + tile).min(len);
+        let start_eff = end.saturating_sub(tile);
+        let denoised = run_vector_estimator(
+            vector_est_ort,
+            xt.slice(s![.., .., start_eff..end]).to_owned(),
+            text_emb,
+            style_ttl_value,
+            latent_mask.slice(s![.., .., start_eff..end]).to_owned(),
+            text_mask,
+            steps,
+        )?;
+        // Linear ramps over the overlap on interior edges; weights are renormalized below
+        for j in 0..end - start_eff {
+            let mut w = 1.0f32;
+            if start_eff > 0 && j < overlap {
+                w = (j + 1) as f32 / (overlap + 1) as f32;
+            }
+            if end < len && j + overlap >= end - start_eff {
+                w = w.min((end - start_eff - j) as f32 / (overlap + 1) as f32);
+            }
+            let t = start_eff + j;
+            weight_sum[t] += w;
+            let mut frame = sum.slice_mut(s![.., .., t]);
+            frame.scaled_add(w, &denoised.slice(s![.., .., j]));
+        }
+        if end == len {
+            break;
+        }
+        start += tile - overlap;
+    }
+    for (t, &w) in weight_sum.iter().enumerate() {
+        sum.slice_mut(s![.., .., t]).mapv_inplace(|v| v / w);
+    }
+    Ok(sum)
+}
+
+/// Run the duration predictor and reduce its output to one duration per utterance
+fn predict_duration(
+    dp_ort: &mut Session,
+    text_ids_value: &Tensor<i64>,
+    style_dp_value: &Tensor<f32>,
+    text_mask_value: &Tensor<f32>,
+    text_mask: &Array3<f32>,
+) -> Result<Vec<f32>> {
+    let dp_outputs = dp_ort.run(ort::inputs!{
+        "text_ids" => text_ids_value,
+        "style_dp" => style_dp_value,
+        "text_mask" => text_mask_value
+    })?;
+    let (duration_shape, duration_data) = dp_outputs["duration"].try_extract_tensor::<f32>()?;
+    per_utterance_duration(duration_shape, duration_data, text_mask)
+}
+
+/// Run the text encoder, returning the text embedding
+fn encode_text(
+    text_enc_ort: &mut Session,
+    text_ids_value: &Tensor<i64>,
+    style_ttl_value: &Tensor<f32>,
+    text_mask_value: &Tensor<f32>,
+) -> Result<Array3<f32>> {
+    let text_enc_outputs = text_enc_ort.run(ort::inputs!{
+        "text_ids" => text_ids_value,
+        "style_ttl" => style_ttl_value,
+        "text_mask" => text_mask_value
+    })?;
+    let (text_emb_shape, text_emb_data) = text_enc_outputs["text_emb"].try_extract_tensor::<f32>()?;
+    Ok(Array3::from_shape_vec(
+        (text_emb_shape[0] as usize, text_emb_shape[1] as usize, text_emb_shape[2] as usize),
+        text_emb_data.to_vec()
+    )?)
+}
+
+/// Static dimensions of a tensor input/output (-1 marks dynamic axes)
+fn tensor_dims(value_type: &ValueType) -> Option<Vec<i64>> {
+    match value_type {
+        ValueType::Tensor { shape, .. } => Some(shape.to_vec()),
+        _ => None,
+    }
+}
+
+/// Execution provider the ONNX sessions run on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    #[default]
+    Cpu,
+    /// Core ML on Apple platforms (Neural Engine, GPU or CPU, as Core ML decides)
+    CoreMl,
+    /// NVIDIA GPU with this device id
+    Cuda(i32),
+}
+
+impl Backend {
+    /// Execution provider name, as reported by `LazySession::provider`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Cpu => "CPU",
+            Backend::CoreMl => "CoreML",
+            Backend::Cuda(_) => "CUDA",
+        }
+    }
+
+    /// Provider to register on a session builder; None for the default CPU provider
+    fn provider(&self) -> Option<ExecutionProviderDispatch> {
+        match *self {
+            Backend::Cpu => None,
+            Backend::CoreMl => Some(CoreMLExecutionProvider::default().build()),
+            Backend::Cuda(device_id) => Some(CUDAExecutionProvider::default().with_device_id(device_id).build()),
+        }
+    }
+}
+
+impl std::fmt::Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Backend::Cuda(device_id) => write!(f, "CUDA device {}", device_id),
+            backend => f.write_str(backend.name()),
+        }
+    }
+}
+
+/// An ONNX session that is built from its model file on first use
+pub struct LazySession {
+    path: PathBuf,
+    bytes: Option<Vec<u8>>,
+    profile_prefix: Option<PathBuf>,
+    backend: Backend,
+    /// The backend's provider was registered when the session was built
+    on_backend: bool,
+    session: Option<Session>,
+}
+
+impl LazySession {
+    /// Defer loading `path` until the session is first needed
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        LazySession {
+            path: path.as_ref().to_path_buf(),
+            bytes: None,
+            profile_prefix: None,
+            backend: Backend::Cpu,
+            on_backend: false,
+            session: None,
+        }
+    }
+
+    /// Defer building a session from in-memory model bytes; `name` is used in errors
+    pub fn from_bytes<P: AsRef<Path>>(name: P, bytes: Vec<u8>) -> Self {
+        LazySession {
+            path: name.as_ref().to_path_buf(),
+            bytes: Some(bytes),
+            profile_prefix: None,
+            backend: Backend::Cpu,
+            on_backend: false,
+            session: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.session.is_some()
+    }
+
+    /// Record per-op ONNX Runtime profiling to `<prefix>_<timestamp>.json`
+    ///
+    /// Takes effect only if the session has not been loaded yet; the file is complete
+    /// once `end_profiling` is called.
+    pub fn with_profiling<P: AsRef<Path>>(mut self, prefix: P) -> Self {
+        self.profile_prefix = Some(prefix.as_ref().to_path_buf());
+        self
+    }
+
+    /// Run on `backend` instead of the CPU
+    ///
+    /// Takes effect only if the session has not been loaded yet. If the backend's provider
+    /// cannot be registered when loading, the session falls back to CPU with a warning.
+    pub fn with_backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// The backend requested with `with_backend`
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// Execution provider the loaded session runs on; "CPU" before loading or after a fallback
+    pub fn provider(&self) -> &'static str {
+        if self.on_backend { self.backend.name() } else { "CPU" }
+    }
+
+    /// Stop profiling and return the profile file, if profiling was enabled and the session ran
+    pub fn end_profiling(&mut self) -> Result<Option<PathBuf>> {
+        if self.profile_prefix.is_none() {
+            return Ok(None);
+        }
+        match self.session.as_mut() {
+            Some(session) => Ok(Some(PathBuf::from(session.end_profiling()?))),
+            None => Ok(None),
+        }
+    }
+
+    /// Return the session, loading it if this is the first use
+    pub fn get(&mut self) -> Result<&mut Session> {
+        if self.session.is_none() {
+            let builder = match self.backend.provider() {
+                Some(provider) => match self.builder()?.with_execution_providers([provider.error_on_failure()]) {
+                    Ok(builder) => {
+                        self.on_backend = true;
+                        builder
+                    }
+                    Err(e) => {
+                        eprintln!(
+                            "Warning: {} is unavailable for {} ({}); falling back to CPU",
+                            self.backend,
+                            self.path.display(),
+                            e
+                        );
+                        self.builder()?
+                    }
+                },
+                None => self.builder()?,
+            };
+            let session = match &self.bytes {
+                Some(bytes) => builder.commit_from_memory(bytes),
+                None => builder.commit_from_file(&self.path),
+            }
+            .with_context(|| format!("Failed to load {}", self.path.display()))?;
+            // The session keeps its own copy of the model
+            self.bytes = None;
+            self.session = Some(session);
+        }
+        Ok(self.session.as_mut().expect("session was just initialized"))
+    }
+
+    fn builder(&self) -> Result<SessionBuilder> {
+        let mut builder = Session::builder()?;
+        if let Some(prefix) = &self.profile_prefix {
+            builder = builder.with_profiling(prefix)?;
+        }
+        Ok(builder)
+    }
+}
+
+impl From<Session> for LazySession {
+    /// Wrap an already-built session
+    fn from(session: Session) -> Self {
+        LazySession {
+            path: PathBuf::new(),
+            bytes: None,
+            profile_prefix: None,
+            backend: Backend::Cpu,
+            on_backend: false,
+            session: Some(session),
+        }
+    }
+}
+
+/// Receives vocoded audio of the first batch item as denoising progresses: (step, audio)
+///
+/// Step 0 is the initial noise; step k follows the k-th denoising step.
+pub type StepPreviewHook = Box<dyn FnMut(usize, &[f32]) + Send>;
+
+/// Speech recognizer used for round-trip checks: (audio, sample_rate) -> transcript
+pub type TranscribeHook = Box<dyn Fn(&[f32], i32) -> String + Send>;
+
+/// An input text and what the transcribe hook heard in its synthesized audio
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transcription {
+    pub text: String,
+    pub transcript: String,
+}
+
+/// What to do when a predicted duration exceeds the configured maximum output length
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverlongPolicy {
+    /// Clamp the duration to the cap with a warning; the speech is compressed to fit
+    #[default]
+    Truncate,
+    /// Fail with an error
+    Error,
+}
+
+pub struct TextToSpeech {
+    cfgs: Config,
+    text_processor: UnicodeProcessor,
+    dp_ort: LazySession,
+    text_enc_ort: LazySession,
+    vector_est_ort: LazySession,
+    vocoder_ort: LazySession,
+    pub sample_rate: i32,
+    dump_latent_path: Option<PathBuf>,
+    rng: Option<Box<dyn RngCore + Send>>,
+    item_seed: Option<u64>,
+    metrics: Box<dyn MetricsSink>,
+    chunk_pauses: Option<ChunkPauses>,
+    chunk_compress: Option<i32>,
+    latent_dim: Option<i32>,
+    transcribe_hook: Option<TranscribeHook>,
+    transcriptions: Vec<Transcription>,
+    latent_rounding: LatentRounding,
+    max_latent_len: usize,
+    max_wav_seconds: Option<f32>,
+    overlong_policy: OverlongPolicy,
+    step_preview: Option<StepPreviewHook>,
+    vocoder_split: Option<usize>,
+    vocoder_pool: Vec<LazySession>,
+    latent_norm_limit: Option<f32>,
+    latent_tile: Option<(usize, usize)>,
+    concurrent_encode: bool,
+}
+
+impl TextToSpeech {
+    pub fn new(
+        cfgs: Config,
+        text_processor: UnicodeProcessor,
+        dp_ort: impl Into<LazySession>,
+        text_enc_ort: impl Into<LazySession>,
+        vector_est_ort: impl Into<LazySession>,
+        vocoder_ort: impl Into<LazySession>,
+    ) -> Self {
+        let sample_rate = cfgs.ae.sample_rate;
+        TextToSpeech {
+            cfgs,
+            text_processor,
+            dp_ort: dp_ort.into(),
+            text_enc_ort: text_enc_ort.into(),
+            vector_est_ort: vector_est_ort.into(),
+            vocoder_ort: vocoder_ort.into(),
+            sample_rate,
+            dump_latent_path: None,
+            rng: None,
+            item_seed: None,
+            metrics: Box::new(NoopMetrics),
+            chunk_pauses: None,
+            chunk_compress: None,
+            latent_dim: None,
+            transcribe_hook: None,
+            transcriptions: Vec::new(),
+            latent_rounding: LatentRounding::default(),
+            max_latent_len: DEFAULT_MAX_LATENT_LEN,
+            max_wav_seconds: None,
+            overlong_policy: OverlongPolicy::default(),
+            step_preview: None,
+            vocoder_split: None,
+            vocoder_pool: Vec::new(),
+            latent_norm_limit: None,
+            latent_tile: None,
+            concurrent_encode: true,
+        }
+    }
+
+    /// Run the duration predictor and text encoder on two threads (the default) or one
+    /// after the other
+    ///
+    /// Both read only the shared inputs. Sequential runs exist to measure what the
+    /// overlap saves: compare the `encode` stage reported to the metrics sink.
+    pub fn set_concurrent_encode(&mut self, concurrent: bool) {
+        self.concurrent_encode = concurrent;
+    }
+
+    /// Vocode the latent after every denoising step and pass it to `hook`
+    ///
+    /// Costs one extra vocoder pass per step (plus one for the initial noise), so
+    /// synthesis becomes several times slower. None disables it.
+    pub fn set_step_preview(&mut self, hook: Option<StepPreviewHook>) {
+        self.step_preview = hook;
+    }
+
+    /// Vocode batches in sub-batches of `split` items, each cut to its own longest latent,
+    /// spread over `threads` vocoder sessions
+    ///
+    /// None (the default) vocodes the whole padded batch in one run. Each extra thread
+    /// loads another copy of the vocoder, since a session runs one inference at a time; this
+    /// needs the vocoder as a file on disk, not inside a bundle. The padded tail of each item's
+    /// waveform is silent rather than vocoded padding.
+    pub fn set_vocoder_split(&mut self, split: Option<usize>, threads: usize) -> Result<()> {
+        if split == Some(0) {
+            bail!("Vocoder sub-batch size must be at least 1");
+        }
+        let extra = if split.is_some() { threads.max(1) - 1 } else { 0 };
+        let path = self.vocoder_ort.path().to_path_buf();
+        if extra > 0 && !path.is_file() {
+            bail!("Parallel vocoding loads extra vocoder sessions from a file, but {} is not one", path.display());
+        }
+        let backend = self.vocoder_ort.backend();
+        self.vocoder_pool = (0..extra).map(|_| LazySession::new(&path).with_backend(backend)).collect();
+        self.vocoder_split = split;
+        Ok(())
+    }
+
+    /// Vocode `latent` in sub-batches trimmed to their latent lengths, concurrently when
+    /// extra vocoder sessions are loaded, and lay the items out as the full-batch run would
+    fn vocode_split(&mut self, latent: &Array3<f32>, latent_mask: &Array3<f32>, split: usize) -> Result<Vec<f32>> {
+        let (bsz, _, latent_len) = latent.dim();
+        let lengths: Vec<usize> = (0..bsz)
+            .map(|b| latent_mask.slice(s![b, 0, ..]).sum() as usize)
+            .collect();
+        let groups: Vec<std::ops::Range<usize>> = (0..bsz).step_by(split).map(|start| start..(start + split).min(bsz)).collect();
+        let group_lens: Vec<usize> = groups
+            .iter()
+            .map(|g| lengths[g.clone()].iter().copied().max().unwrap_or(0).max(1))
+            .collect();
+
+        let mut sessions = vec![self.vocoder_ort.get()?];
+        for session in self.vocoder_pool.iter_mut() {
+            sessions.push(session.get()?);
+        }
+        let n_sessions = sessions.len();
+
+        let mut outputs: Vec<Vec<f32>> = vec![Vec::new(); groups.len()];
+        std::thread::scope(|scope| -> Result<()> {
+            let handles: Vec<_> = sessions
+                .into_iter()
+                .enumerate()
+                .map(|(t, session)| {
+                    let (groups, group_lens) = (&groups, &group_lens);
+                    scope.spawn(move || -> Result<Vec<(usize, Vec<f32>)>> {
+                        (t..groups.len())
+                            .step_by(n_sessions)
+                            .map(|g| {
+                                let part = latent.slice(s![groups[g].clone(), .., ..group_lens[g]]).to_owned();
+                                Ok((g, run_vocoder(session, part)?))
+                            })
+                            .collect()
+                    })
+                })
+                .collect();
+            for handle in handles {
+                let done = handle
+                    .join()
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("Vocoder thread panicked")))?;
+                for (g, wav) in done {
+                    outputs[g] = wav;
+                }
+            }
+            Ok(())
+        })?;
+
+        // Samples per latent frame, from the first sub-batch's output
+        let samples_per_frame = outputs[0].len() / (groups[0].len() * group_lens[0]);
+        let stride = latent_len * samples_per_frame;
+        let mut wav = vec![0.0f32; bsz * stride];
+        for ((group, group_len), output) in groups.iter().zip(&group_lens).zip(&outputs) {
+            let item_len = group_len * samples_per_frame;
+            for (k, b) in group.clone().enumerate() {
+                let item = &output[k * item_len..(k + 1) * item_len];
+                wav[b * stride..b * stride + item_len].copy_from_slice(item);
+            }
+        }
+        Ok(wav)
+    }
+
+    /// Denoise latents longer than `tile` frames in overlapping tiles along the time axis
+    ///
+    /// Each tile sees the full text but only its own frames, and adjacent tiles are
+    /// crossfaded over `overlap` frames, so peak memory in the vector estimator follows the
+    /// tile rather than the utterance. Only valid for models whose attention over the latent
+    /// is local; with global attention the tiles disagree and the output has audible seams.
+    /// None (the default) denoises the whole latent at once.
+    pub fn set_latent_tile(&mut self, tile: Option<usize>, overlap: usize) -> Result<()> {
+        if let Some(tile) = tile {
+            if overlap >= tile {
+                bail!("Latent tile overlap ({}) must be smaller than the tile ({})", overlap, tile);
+            }
+        }
+        self.latent_tile = tile.map(|tile| (tile, overlap));
+        Ok(())
+    }
+
+    /// Log the latent's L2 norm and max abs value after every denoising step
+    ///
+    /// Warns when the norm exceeds `limit` times the initial noise norm (or stops being
+    /// finite), a sign the denoising is diverging. None (the default) disables it.
+    pub fn set_latent_monitor(&mut self, limit: Option<f32>) {
+        self.latent_norm_limit = limit;
+    }
+
+    /// Print the latent statistics for `step`; `initial_norm` is the noise norm at step 0
+    fn monitor_latent(&self, step: usize, latent: &Array3<f32>, initial_norm: f32) {
+        let Some(limit) = self.latent_norm_limit else {
+            return;
+        };
+        let norm = latent.iter().map(|v| v * v).sum::<f32>().sqrt();
+        let max_abs = latent.iter().fold(0.0f32, |m, v| m.max(v.abs()));
+        println!("  step {:>3}: |xt|={:.3}, max|xt|={:.4}", step, norm, max_abs);
+        if !norm.is_finite() || norm > limit * initial_norm {
+            eprintln!(
+                "Warning: latent norm {:.3} at step {} exceeds {}x the initial {:.3}; denoising may be diverging",
+                norm, step, limit, initial_norm
+            );
+        }
+    }
+
+    /// Run the vocoder on `latent` and hand the first item's audio to the step preview hook
+    fn preview_step(&mut self, step: usize, latent: &Array3<f32>, duration: f32) -> Result<()> {
+        if self.step_preview.is_none() {
+            return Ok(());
+        }
+        let latent_value = Value::from_array(latent.slice(s![0..1, .., ..]).to_owned())?;
+        let outputs = self.vocoder_ort.get()?.run(ort::inputs!{
+            "latent" => &latent_value
+        })?;
+        let (_, wav_data) = outputs["wav_tts"].try_extract_tensor::<f32>()?;
+        let len = ((self.sample_rate as f32 * duration) as usize).min(wav_data.len());
+        if let Some(hook) = self.step_preview.as_mut() {
+            hook(step, &wav_data[..len]);
+        }
+        Ok(())
+    }
+
+    /// Set how the latent length rounds partial chunks (default: ceil, as the released models expect)
+    pub fn set_latent_rounding(&mut self, rounding: LatentRounding) {
+        self.latent_rounding = rounding;
+    }
+
+    /// Cap the latent length of one inference (default `DEFAULT_MAX_LATENT_LEN` frames)
+    ///
+    /// A predicted duration needing more frames fails with an error rather than allocating.
+    pub fn set_max_latent_len(&mut self, max_latent_len: usize) {
+        self.max_latent_len = max_latent_len;
+    }
+
+    /// Cap each output at `max_seconds` (default: no cap), handling longer audio per `policy`
+    ///
+    /// The cap applies to each batch item and to the whole of a chunked `call`, pauses
+    /// included. `Error` fails as soon as a predicted duration (or the running total)
+    /// exceeds it, before that audio is vocoded or emitted; `Truncate` cuts the waveform
+    /// at the cap with a warning.
+    pub fn set_max_wav_seconds(&mut self, max_seconds: Option<f32>, policy: OverlongPolicy) {
+        self.max_wav_seconds = max_seconds;
+        self.overlong_policy = policy;
+    }
+
+    /// Apply the output length cap to (speed-adjusted) predicted durations
+    ///
+    /// Errors under `OverlongPolicy::Error`; under `Truncate` clamps the durations, which
+    /// set the output lengths the waveform is cut to. The latent keeps the full duration.
+    fn cap_durations(&self, duration: &mut [f32]) -> Result<()> {
+        let Some(max_seconds) = self.max_wav_seconds else {
+            return Ok(());
+        };
+        for (i, dur) in duration.iter_mut().enumerate() {
+            if *dur <= max_seconds {
+                continue;
+            }
+            match self.overlong_policy {
+                OverlongPolicy::Error => bail!(
+                    "Predicted duration {:.2}s of batch item {} exceeds the maximum of {:.2}s",
+                    dur, i, max_seconds
+                ),
+                OverlongPolicy::Truncate => {
+                    eprintln!(
+                        "Warning: predicted duration {:.2}s of batch item {} exceeds the maximum; truncating at {:.2}s",
+                        dur, i, max_seconds
+                    );
+                    *dur = max_seconds;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Release every ONNX session in a fixed order: the vocoder copies, then the stages
+    /// from last to first
+    ///
+    /// Dropping at a known point on the calling thread, rather than during process exit,
+    /// avoids racing ONNX Runtime's own global teardown.
+    pub fn shutdown(self) {
+        let TextToSpeech { dp_ort, text_enc_ort, vector_est_ort, vocoder_ort, vocoder_pool, .. } = self;
+        drop(vocoder_pool);
+        drop(vocoder_ort);
+        drop(vector_est_ort);
+        drop(text_enc_ort);
+        drop(dp_ort);
+    }
+
+    /// Execution provider of each stage's session (see `LazySession::provider`), in pipeline order
+    pub fn session_providers(&self) -> [(&'static str, &'static str); 4] {
+        [
+            ("duration_predictor", self.dp_ort.provider()),
+            ("text_encoder", self.text_enc_ort.provider()),
+            ("vector_estimator", self.vector_est_ort.provider()),
+            ("vocoder", self.vocoder_ort.provider()),
+        ]
+    }
+
+    /// Finish ONNX Runtime profiling and return the profile files written
+    ///
+    /// Only sessions loaded with profiling enabled and actually used produce a file.
+    pub fn end_profiling(&mut self) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for session in [&mut self.dp_ort, &mut self.text_enc_ort, &mut self.vector_est_ort, &mut self.vocoder_ort] {
+            files.extend(session.end_profiling()?);
+        }
+        Ok(files)
+    }
+
+    /// Pass every synthesized utterance to `hook` for an ASR round-trip check
+    ///
+    /// This is only an integration point: no recognizer ships with the crate. Each
+    /// result is logged and kept until `take_transcriptions`; None removes the hook.
+    pub fn set_transcribe_hook(&mut self, hook: Option<TranscribeHook>) {
+        self.transcribe_hook = hook;
+    }
+
+    /// Round-trip results recorded since the last call, in synthesis order
+    pub fn take_transcriptions(&mut self) -> Vec<Transcription> {
+        std::mem::take(&mut self.transcriptions)
+    }
 
-    for para in paragraphs {
-        let para = para.trim();
-        if para.is_empty() {
-            continue;
+    /// Run the transcribe hook, if any, on one utterance's trimmed audio
+    fn check_round_trip(&mut self, text: &str, wav: &[f32]) {
+        if let Some(hook) = &self.transcribe_hook {
+            let transcript = hook(wav, self.sample_rate);
+            println!("Round-trip: {:?} -> {:?}", text, transcript);
+            self.transcriptions.push(Transcription {
+                text: text.to_string(),
+                transcript,
+            });
         }
+    }
 
-        if para.len() <= max_len {
-            chunks.push(para.to_string());
-            continue;
+    /// Override the config's `chunk_compress_factor` for latent sizing (None restores it)
+    ///
+    /// For experiments only: the models were trained with the config value, so any
+    /// other factor changes the latent shape and degrades or breaks the output.
+    pub fn set_chunk_compress(&mut self, factor: Option<i32>) -> Result<()> {
+        if let Some(factor) = factor {
+            if factor <= 0 {
+                bail!("chunk_compress must be positive, got {}", factor);
+            }
+            if factor != self.cfgs.ttl.chunk_compress_factor {
+                eprintln!(
+                    "Warning: chunk_compress {} differs from the trained value {}; output will degrade",
+                    factor, self.cfgs.ttl.chunk_compress_factor
+                );
+            }
         }
+        self.chunk_compress = factor;
+        Ok(())
+    }
 
-        // Split by sentences
-        let sentences = split_sentences(para);
-        let mut current = String::new();
-        let mut current_len = 0;
+    /// Chunk compression factor in effect: the override if set, else the config value
+    pub fn chunk_compress(&self) -> i32 {
+        self.chunk_compress.unwrap_or(self.cfgs.ttl.chunk_compress_factor)
+    }
 
-        for sentence in sentences {
-            let sentence = sentence.trim();
-            if sentence.is_empty() {
-                continue;
+    /// Override the config's `latent_dim` (None restores it)
+    ///
+    /// For matching custom exports only. The vector estimator must accept
+    /// `latent_dim * chunk_compress` latent channels; this is checked before denoising.
+    pub fn set_latent_dim(&mut self, latent_dim: Option<i32>) -> Result<()> {
+        if let Some(latent_dim) = latent_dim {
+            if latent_dim <= 0 {
+                bail!("latent_dim must be positive, got {}", latent_dim);
             }
+        }
+        self.latent_dim = latent_dim;
+        Ok(())
+    }
 
-            let sentence_len = sentence.len();
-            if sentence_len > max_len {
-                // If sentence is longer than max_len, split by comma or space
-                if !current.is_empty() {
-                    chunks.push(current.trim().to_string());
-                    current.clear();
-                    current_len = 0;
-                }
+    /// Latent dimension in effect: the override if set, else the config value
+    pub fn latent_dim(&self) -> i32 {
+        self.latent_dim.unwrap_or(self.cfgs.ttl.latent_dim)
+    }
 
-                // Try splitting by comma
-                let parts: Vec<&str> = sentence.split(',').collect();
-                for part in parts {
-                    let part = part.trim();
-                    if part.is_empty() {
-                        continue;
-                    }
+    /// Map characters beyond the end of the indexer to `id` instead of dropping them to OOV
+    pub fn set_out_of_range_id(&mut self, id: Option<i64>) {
+        self.text_processor.set_out_of_range_id(id);
+    }
 
-                    let part_len = part.len();
-                    if part_len > max_len {
-                        // Split by space as last resort
-                        let words: Vec<&str> = part.split_whitespace().collect();
-                        let mut word_chunk = String::new();
-                        let mut word_chunk_len = 0;
+    /// Choose the silence between chunks of long text by punctuation
+    ///
+    /// None (the default) uses the fixed `silence_duration` passed to `call`.
+    pub fn set_chunk_pauses(&mut self, pauses: Option<ChunkPauses>) {
+        self.chunk_pauses = pauses;
+    }
 
-                        for word in words {
-                            let word_len = word.len();
-                            if word_chunk_len + word_len + 1 > max_len && !word_chunk.is_empty() {
-                                chunks.push(word_chunk.trim().to_string());
-                                word_chunk.clear();
-                                word_chunk_len = 0;
-                            }
+    /// Report stage timings and real-time factor of every inference to `sink`
+    pub fn set_metrics_sink(&mut self, sink: Box<dyn MetricsSink>) {
+        self.metrics = sink;
+    }
 
-                            if !word_chunk.is_empty() {
-                                word_chunk.push(' ');
-                                word_chunk_len += 1;
-                            }
-                            word_chunk.push_str(word);
-                            word_chunk_len += word_len;
-                        }
+    /// Use a caller-provided RNG for the initial noise (None restores `thread_rng`)
+    pub fn set_rng(&mut self, rng: Option<Box<dyn RngCore + Send>>) {
+        self.rng = rng;
+    }
 
-                        if !word_chunk.is_empty() {
-                            chunks.push(word_chunk.trim().to_string());
-                        }
-                    } else {
-                        if current_len + part_len + 1 > max_len && !current.is_empty() {
-                            chunks.push(current.trim().to_string());
-                            current.clear();
-                            current_len = 0;
-                        }
+    /// Make the next inferences reproducible from `seed`, or random again with None
+    ///
+    /// Replaces any RNG from `set_rng` and clears `set_item_seed`. The sessions are
+    /// untouched, so a long-lived instance can reseed per request.
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.item_seed = None;
+        self.rng = seed.map(|seed| Box::new(StdRng::seed_from_u64(seed)) as Box<dyn RngCore + Send>);
+    }
 
-                        if !current.is_empty() {
-                            current.push_str(", ");
-                            current_len += 2;
-                        }
-                        current.push_str(part);
-                        current_len += part_len;
-                    }
-                }
-                continue;
-            }
+    /// Seed each batch item's noise independently from `seed` and its index
+    ///
+    /// Takes precedence over `set_rng`; see `derive_item_seed`. None disables it.
+    pub fn set_item_seed(&mut self, seed: Option<u64>) {
+        self.item_seed = seed;
+    }
 
-            if current_len + sentence_len + 1 > max_len && !current.is_empty() {
-                chunks.push(current.trim().to_string());
-                current.clear();
-                current_len = 0;
-            }
+    pub fn text_processor(&self) -> &UnicodeProcessor {
+        &self.text_processor
+    }
 
-            if !current.is_empty() {
-                current.push(' ');
-                current_len += 1;
+    pub fn text_processor_mut(&mut self) -> &mut UnicodeProcessor {
+        &mut self.text_processor
+    }
+
+    /// Sample rate declared in the vocoder's ONNX metadata (`sample_rate` or `sampling_rate`), if any
+    ///
+    /// Loads the vocoder session if it is not loaded yet.
+    pub fn vocoder_sample_rate(&mut self) -> Result<Option<i32>> {
+        let metadata = self.vocoder_ort.get()?.metadata()?;
+        for key in ["sample_rate", "sampling_rate"] {
+            if let Some(value) = metadata.custom(key)? {
+                let rate = value
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("Vocoder metadata {} is not an integer: {:?}", key, value))?;
+                return Ok(Some(rate));
             }
-            current.push_str(sentence);
-            current_len += sentence_len;
         }
+        Ok(None)
+    }
 
-        if !current.is_empty() {
-            chunks.push(current.trim().to_string());
+    /// Fail early when the text encoder's embedding width differs from the vector estimator's
+    ///
+    /// Mixed-version model sets otherwise fail with an opaque shape error inside the
+    /// denoising loop. A vector estimator with a dynamic `text_emb` width is not checked.
+    fn check_text_emb_dim(&mut self, emb_dim: usize) -> Result<()> {
+        let expected = self
+            .vector_est_ort
+            .get()?
+            .inputs
+            .iter()
+            .find(|i| i.name == "text_emb")
+            .and_then(|i| tensor_dims(&i.input_type))
+            .and_then(|dims| dims.get(1).copied())
+            .filter(|&d| d > 0);
+        match expected {
+            Some(expected) if expected as usize != emb_dim => bail!(
+                "Text encoder produces text_emb of dimension {} but the vector estimator expects {}; \
+                 the models appear to come from different exports",
+                emb_dim,
+                expected
+            ),
+            _ => Ok(()),
         }
     }
 
-    if chunks.is_empty() {
-        vec![String::new()]
-    } else {
-        chunks
+    /// Fail early when the vector estimator declares a latent channel count other than `channels`
+    fn check_latent_channels(&mut self, channels: usize) -> Result<()> {
+        let expected = self
+            .vector_est_ort
+            .get()?
+            .inputs
+            .iter()
+            .find(|i| i.name == "noisy_latent")
+            .and_then(|i| tensor_dims(&i.input_type))
+            .and_then(|dims| dims.get(1).copied())
+            .filter(|&d| d > 0);
+        match expected {
+            Some(expected) if expected as usize != channels => bail!(
+                "Latent has {} channels (latent_dim {} x chunk_compress {}) but the vector estimator expects {}",
+                channels,
+                self.latent_dim(),
+                self.chunk_compress(),
+                expected
+            ),
+            _ => Ok(()),
+        }
     }
-}
 
-fn split_sentences(text: &str) -> Vec<String> {
-    // Rust's regex doesn't support lookbehind, so we use a simpler approach
-    // Split on sentence boundaries and then check if they're abbreviations
-    let re = Regex::new(r"([.!?])\s+").unwrap();
-    
-    // Find all matches
-    let matches: Vec<_> = re.find_iter(text).collect();
-    if matches.is_empty() {
-        return vec![text.to_string()];
-    }
-    
-    let mut sentences = Vec::new();
-    let mut last_end = 0;
-    
-    for m in matches {
-        // Get the text before the punctuation
-        let before_punc = &text[last_end..m.start()];
-        
-        // Check if this ends with an abbreviation
-        let mut is_abbrev = false;
-        for abbrev in ABBREVIATIONS {
-            let combined = format!("{}{}", before_punc.trim(), &text[m.start()..m.start()+1]);
-            if combined.ends_with(abbrev) {
-                is_abbrev = true;
-                break;
-            }
+    /// Run only the vocoder on a latent of shape (batch, latent_dim * chunk_compress, frames)
+    ///
+    /// The counterpart of `set_dump_latent` for isolating the final stage: no text, style or
+    /// denoising is involved. Returns the items' waveforms back to back, each the same length.
+    pub fn vocode(&mut self, latent: Array3<f32>) -> Result<Vec<f32>> {
+        let (bsz, channels, frames) = latent.dim();
+        let expected = self
+            .vocoder_ort
+            .get()?
+            .inputs
+            .iter()
+            .find(|i| i.name == "latent")
+            .and_then(|i| tensor_dims(&i.input_type))
+            .and_then(|dims| dims.get(1).copied())
+            .filter(|&d| d > 0)
+            .map_or((self.latent_dim() * self.chunk_compress()) as usize, |d| d as usize);
+        if bsz == 0 || frames == 0 {
+            bail!("Latent of shape {:?} is empty", latent.dim());
         }
-        
-        if !is_abbrev {
-            // This is a real sentence boundary
-            sentences.push(text[last_end..m.end()].to_string());
-            last_end = m.end();
+        if channels != expected {
+            bail!("Latent has {} channels but the vocoder expects {}", channels, expected);
         }
+        run_vocoder(self.vocoder_ort.get()?, latent)
     }
-    
-    // Add the remaining text
-    if last_end < text.len() {
-        sentences.push(text[last_end..].to_string());
+
+    /// Compare the config's sample rate with the vocoder's declared rate
+    ///
+    /// A mismatch makes audio play at the wrong speed and pitch. It is a warning, or an
+    /// error when `strict`; vocoders without rate metadata pass.
+    pub fn check_sample_rate(&mut self, strict: bool) -> Result<()> {
+        let Some(vocoder_rate) = self.vocoder_sample_rate()? else {
+            return Ok(());
+        };
+        if vocoder_rate != self.sample_rate {
+            let message = format!(
+                "tts.json declares sample_rate {} but the vocoder was exported for {}; audio will play at the wrong speed and pitch",
+                self.sample_rate, vocoder_rate
+            );
+            if strict {
+                bail!(message);
+            }
+            eprintln!("Warning: {}", message);
+        }
+        Ok(())
     }
-    
-    if sentences.is_empty() {
-        vec![text.to_string()]
-    } else {
-        sentences
+
+    /// Model configuration loaded from `tts.json`
+    pub fn config(&self) -> &Config {
+        &self.cfgs
     }
-}
 
-// ============================================================================
-// Utility Functions
-// ============================================================================
+    /// The config with runtime overrides (such as `set_chunk_compress`) applied
+    pub fn effective_config(&self) -> Config {
+        let mut cfgs = self.cfgs.clone();
+        cfgs.ttl.chunk_compress_factor = self.chunk_compress();
+        cfgs.ttl.latent_dim = self.latent_dim();
+        cfgs
+    }
 
-pub fn timer<F, T>(name: &str, f: F) -> Result<T>
-where
-    F: FnOnce() -> Result<T>,
-{
-    let start = std::time::Instant::now();
-    println!("{}...", name);
-    let result = f()?;
-    let elapsed = start.elapsed().as_secs_f64();
-    println!("  -> {} completed in {:.2} sec", name, elapsed);
-    Ok(result)
-}
+    /// Predicted duration in seconds of each item of a batch, after speed and any
+    /// `set_max_wav_seconds` cap; only the duration predictor runs
+    pub fn predict_durations(
+        &mut self,
+        text_list: &[String],
+        lang_list: &[String],
+        style: &Style,
+        speed: f32,
+    ) -> Result<Vec<f32>> {
+        let (text_ids, text_mask) = self.text_processor.call(text_list, lang_list)?;
+        let text_ids_value = Value::from_array(text_ids_to_array(&text_ids)?)?;
+        let text_mask_value = Value::from_array(text_mask.clone())?;
+        let style_dp_value = Value::from_array(style.dp.clone())?;
+        let mut duration: Vec<f32> = predict_duration(
+            self.dp_ort.get()?,
+            &text_ids_value,
+            &style_dp_value,
+            &text_mask_value,
+            &text_mask,
+        )?
+        .into_iter()
+        .map(|d| d / speed)
+        .collect();
+        self.cap_durations(&mut duration)?;
+        Ok(duration)
+    }
 
-pub fn sanitize_filename(text: &str, max_len: usize) -> String {
-    // Take first max_len characters (Unicode code points, not bytes)
-    text.chars()
-        .take(max_len)
-        .map(|c| {
-            // is_alphanumeric() works with all Unicode letters and digits
-            if c.is_alphanumeric() {
-                c
-            } else {
-                '_'
+    /// Predicted duration of `call` on `text`: its chunks plus the pauses between them
+    pub fn predict_call_duration(
+        &mut self,
+        text: &str,
+        lang: &str,
+        style: &Style,
+        speed: f32,
+        silence_duration: f32,
+    ) -> Result<f32> {
+        let max_len = if lang == "ko" { 120 } else { 300 };
+        let chunks = chunk_text_with_breaks(text, Some(max_len));
+        let pauses = self.chunk_pauses.unwrap_or(ChunkPauses::uniform(silence_duration));
+        let mut total = 0.0;
+        for (i, (chunk, _)) in chunks.iter().enumerate() {
+            total += self.predict_durations(std::slice::from_ref(chunk), &[lang.to_string()], style, speed)?[0];
+            if i > 0 {
+                let (prev_chunk, paragraph_end) = &chunks[i - 1];
+                total += pauses.after(prev_chunk, *paragraph_end);
             }
+        }
+        Ok(total)
+    }
+
+    /// Estimate tensor memory for synthesizing `text_list` as one batch
+    ///
+    /// Only the duration predictor is run; the other sizes follow from the config.
+    pub fn estimate_memory(
+        &mut self,
+        text_list: &[String],
+        lang_list: &[String],
+        style: &Style,
+        speed: f32,
+    ) -> Result<MemoryEstimate> {
+        let (_, text_mask) = self.text_processor.call(text_list, lang_list)?;
+        let bsz = text_list.len();
+        let text_len = text_mask.shape()[2];
+        let duration = self.predict_durations(text_list, lang_list, style, speed)?;
+
+        let emb_dim = self
+            .text_enc_ort
+            .get()?
+            .outputs
+            .iter()
+            .find(|o| o.name == "text_emb")
+            .and_then(|o| tensor_dims(&o.output_type))
+            .and_then(|dims| dims.get(1).copied())
+            .filter(|&d| d > 0)
+            .context("Text encoder does not declare a static text_emb dimension")?;
+
+        let LengthInfo {
+            chunk_size,
+            latent_dim,
+            latent_len,
+            ..
+        } = compute_lengths(
+            &duration,
+            self.sample_rate,
+            self.cfgs.ae.base_chunk_size,
+            self.chunk_compress(),
+            self.latent_dim(),
+            self.latent_rounding,
+            self.max_latent_len,
+        )?;
+
+        let f32_size = std::mem::size_of::<f32>();
+        let text_emb_bytes = bsz * emb_dim as usize * text_len * f32_size;
+        let latent_bytes = bsz * latent_dim * latent_len * f32_size;
+        let wav_bytes = bsz * latent_len * chunk_size * f32_size;
+
+        Ok(MemoryEstimate {
+            text_emb_bytes,
+            latent_bytes,
+            wav_bytes,
+            total_bytes: text_emb_bytes + latent_bytes + wav_bytes,
         })
-        .collect()
-}
+    }
 
-// ============================================================================
-// ONNX Runtime Integration
-// ============================================================================
+    /// Replace the text normalizer applied before preprocessing
+    pub fn set_text_normalizer(&mut self, normalizer: Box<dyn TextNormalizer>) {
+        self.text_processor.set_normalizer(normalizer);
+    }
 
-use ort::{
-    session::Session,
-    value::Value,
-};
+    /// Set how utterances with no mappable characters are handled
+    pub fn set_unmappable_policy(&mut self, policy: UnmappableTextPolicy) {
+        self.text_processor.set_unmappable_policy(policy);
+    }
 
-pub struct Style {
-    pub ttl: Array3<f32>,
-    pub dp: Array3<f32>,
-}
+    /// Pass already-normalized text straight to the unicode mapping
+    pub fn set_skip_normalization(&mut self, skip: bool) {
+        self.text_processor.set_skip_normalization(skip);
+    }
 
-pub struct TextToSpeech {
-    cfgs: Config,
-    text_processor: UnicodeProcessor,
-    dp_ort: Session,
-    text_enc_ort: Session,
-    vector_est_ort: Session,
-    vocoder_ort: Session,
-    pub sample_rate: i32,
-}
+    /// Write the initial noisy latent of each inference to this `.npy` path (None disables)
+    ///
+    /// Each inference overwrites the file, so `call` refuses text that would be split
+    /// into several chunks while this is set.
+    pub fn set_dump_latent(&mut self, path: Option<PathBuf>) {
+        self.dump_latent_path = path;
+    }
 
-impl TextToSpeech {
-    pub fn new(
-        cfgs: Config,
-        text_processor: UnicodeProcessor,
-        dp_ort: Session,
-        text_enc_ort: Session,
-        vector_est_ort: Session,
-        vocoder_ort: Session,
-    ) -> Self {
-        let sample_rate = cfgs.ae.sample_rate;
-        TextToSpeech {
-            cfgs,
-            text_processor,
-            dp_ort,
-            text_enc_ort,
-            vector_est_ort,
-            vocoder_ort,
-            sample_rate,
-        }
+    fn _infer(
+        &mut self,
+        text_list: &[String],
+        lang_list: &[String],
+        style: &Style,
+        total_step: usize,
+        speed: f32,
+        initial_latent: Option<Array3<f32>>,
+    ) -> Result<(Vec<f32>, Vec<f32>)> {
+        let infer_start = Instant::now();
+
+        // Process text
+        let (text_ids, text_mask) = self.text_processor.call(text_list, lang_list)?;
+        self.metrics.record_stage("text_processing", infer_start.elapsed());
+
+        self.infer_ids(&text_ids, text_mask, style, total_step, speed, initial_latent, infer_start)
     }
 
-    fn _infer(
+    /// Synthesize a batch from model text ids, bypassing normalization and the indexer
+    ///
+    /// Rows may differ in length; they are zero-padded and masked. Returns the batched
+    /// audio and per-item durations like `batch`.
+    pub fn call_from_ids(
         &mut self,
-        text_list: &[String],
-        lang_list: &[String],
+        text_ids: &[Vec<i64>],
         style: &Style,
         total_step: usize,
         speed: f32,
     ) -> Result<(Vec<f32>, Vec<f32>)> {
-        let bsz = text_list.len();
+        if text_ids.is_empty() {
+            bail!("No text ids given");
+        }
+        if style.ttl.shape()[0] != text_ids.len() {
+            bail!(
+                "Got {} id sequences but {} voice styles",
+                text_ids.len(),
+                style.ttl.shape()[0]
+            );
+        }
 
-        // Process text
-        let (text_ids, text_mask) = self.text_processor.call(text_list, lang_list)?;
-        
-        let text_ids_array = {
-            let text_ids_shape = (bsz, text_ids[0].len());
-            let mut flat = Vec::new();
-            for row in &text_ids {
-                flat.extend_from_slice(row);
-            }
-            Array::from_shape_vec(text_ids_shape, flat)?
-        };
+        let lengths: Vec<usize> = text_ids.iter().map(|row| row.len()).collect();
+        let max_len = lengths.iter().copied().max().unwrap_or(0);
+        let padded: Vec<Vec<i64>> = text_ids
+            .iter()
+            .map(|row| {
+                let mut row = row.clone();
+                row.resize(max_len, 0);
+                row
+            })
+            .collect();
+
+        self.infer_ids(&padded, get_text_mask(&lengths), style, total_step, speed, None, Instant::now())
+    }
+
+    /// Run the models on padded text ids and their mask
+    #[allow(clippy::too_many_arguments)]
+    fn infer_ids(
+        &mut self,
+        text_ids: &[Vec<i64>],
+        text_mask: Array3<f32>,
+        style: &Style,
+        total_step: usize,
+        speed: f32,
+        initial_latent: Option<Array3<f32>>,
+        infer_start: Instant,
+    ) -> Result<(Vec<f32>, Vec<f32>)> {
+        check_total_step(total_step)?;
+        let bsz = text_ids.len();
+        let text_ids_array = text_ids_to_array(text_ids)?;
 
         let text_ids_value = Value::from_array(text_ids_array)?;
         let text_mask_value = Value::from_array(text_mask.clone())?;
         let style_dp_value = Value::from_array(style.dp.clone())?;
 
-        // Predict duration
-        let dp_outputs = self.dp_ort.run(ort::inputs!{
-            "text_ids" => &text_ids_value,
-            "style_dp" => &style_dp_value,
-            "text_mask" => &text_mask_value
-        })?;
+        let style_ttl_value = Value::from_array(style.ttl.clone())?;
+
+        // Predict duration and encode text, concurrently unless disabled; both only read the shared inputs
+        let stage_start = Instant::now();
+        let dp_ort = self.dp_ort.get()?;
+        let text_enc_ort = self.text_enc_ort.get()?;
+        let (duration_result, text_emb_result) = if self.concurrent_encode {
+            std::thread::scope(|scope| {
+                let dp_handle = scope.spawn(|| {
+                    predict_duration(dp_ort, &text_ids_value, &style_dp_value, &text_mask_value, &text_mask)
+                });
+                let text_emb_result = encode_text(text_enc_ort, &text_ids_value, &style_ttl_value, &text_mask_value);
+                let duration_result = dp_handle
+                    .join()
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("Duration predictor thread panicked")));
+                (duration_result, text_emb_result)
+            })
+        } else {
+            (
+                predict_duration(dp_ort, &text_ids_value, &style_dp_value, &text_mask_value, &text_mask),
+                encode_text(text_enc_ort, &text_ids_value, &style_ttl_value, &text_mask_value),
+            )
+        };
+
+        let mut duration = duration_result?;
+        let text_emb = text_emb_result?;
+        self.check_text_emb_dim(text_emb.shape()[1])?;
+        self.metrics.record_stage("encode", stage_start.elapsed());
 
-        let (_, duration_data) = dp_outputs["duration"].try_extract_tensor::<f32>()?;
-        let mut duration: Vec<f32> = duration_data.to_vec();
-        
         // Apply speed factor to duration
         for dur in duration.iter_mut() {
             *dur /= speed;
         }
+        // The latent covers the full predicted duration so the speech keeps its pace; the
+        // capped durations returned below cut the waveform instead
+        let mut output_duration = duration.clone();
+        self.cap_durations(&mut output_duration)?;
 
-        // Encode text
-        let style_ttl_value = Value::from_array(style.ttl.clone())?;
-        let text_enc_outputs = self.text_enc_ort.run(ort::inputs!{
-            "text_ids" => &text_ids_value,
-            "style_ttl" => &style_ttl_value,
-            "text_mask" => &text_mask_value
-        })?;
+        // Sample noisy latent. This is drawn on every inference and must never be
+        // cached with the text-side results, so repeated runs stay independent
+        // unless the caller seeds the RNG.
+        let (mut xt, latent_mask) = match self.item_seed {
+            Some(seed) => sample_noisy_latent_per_item(
+                &duration,
+                self.sample_rate,
+                self.cfgs.ae.base_chunk_size,
+                self.chunk_compress(),
+                self.latent_dim(),
+                self.latent_rounding,
+                self.max_latent_len,
+                seed,
+            )?,
+            None => sample_noisy_latent(
+                &duration,
+                self.sample_rate,
+                self.cfgs.ae.base_chunk_size,
+                self.chunk_compress(),
+                self.latent_dim(),
+                self.latent_rounding,
+                self.max_latent_len,
+                self.rng.as_deref_mut().map(|rng| rng as &mut dyn RngCore),
+            )?,
+        };
 
-        let (text_emb_shape, text_emb_data) = text_enc_outputs["text_emb"].try_extract_tensor::<f32>()?;
-        let text_emb = Array3::from_shape_vec(
-            (text_emb_shape[0] as usize, text_emb_shape[1] as usize, text_emb_shape[2] as usize),
-            text_emb_data.to_vec()
-        )?;
+        // Replace the sampled noise with a caller-provided latent
+        if let Some(init) = initial_latent {
+            if init.dim() != xt.dim() {
+                bail!(
+                    "Initial latent shape {:?} does not match expected latent shape {:?}",
+                    init.shape(),
+                    xt.shape()
+                );
+            }
+            xt = init;
+            for b in 0..xt.shape()[0] {
+                for d in 0..xt.shape()[1] {
+                    for t in 0..xt.shape()[2] {
+                        xt[[b, d, t]] *= latent_mask[[b, 0, t]];
+                    }
+                }
+            }
+        }
 
-        // Sample noisy latent
-        let (mut xt, latent_mask) = sample_noisy_latent(
-            &duration,
-            self.sample_rate,
-            self.cfgs.ae.base_chunk_size,
-            self.cfgs.ttl.chunk_compress_factor,
-            self.cfgs.ttl.latent_dim,
-        );
+        self.check_latent_channels(xt.dim().1)?;
 
-        // Prepare constant arrays
-        let total_step_array = Array::from_elem(bsz, total_step as f32);
+        if let Some(path) = &self.dump_latent_path {
+            ndarray_npy::write_npy(path, &xt)
+                .with_context(|| format!("Failed to write noisy latent to {}", path.display()))?;
+        }
 
         // Denoising loop
+        self.preview_step(0, &xt, duration[0])?;
+        let initial_norm = xt.iter().map(|v| v * v).sum::<f32>().sqrt();
+        self.monitor_latent(0, &xt, initial_norm);
+        let stage_start = Instant::now();
         for step in 0..total_step {
-            let current_step_array = Array::from_elem(bsz, step as f32);
-
-            let xt_value = Value::from_array(xt.clone())?;
-            let text_emb_value = Value::from_array(text_emb.clone())?;
-            let latent_mask_value = Value::from_array(latent_mask.clone())?;
-            let text_mask_value2 = Value::from_array(text_mask.clone())?;
-            let current_step_value = Value::from_array(current_step_array)?;
-            let total_step_value = Value::from_array(total_step_array.clone())?;
-
-            let vector_est_outputs = self.vector_est_ort.run(ort::inputs!{
-                "noisy_latent" => &xt_value,
-                "text_emb" => &text_emb_value,
-                "style_ttl" => &style_ttl_value,
-                "latent_mask" => &latent_mask_value,
-                "text_mask" => &text_mask_value2,
-                "current_step" => &current_step_value,
-                "total_step" => &total_step_value
-            })?;
-
-            let (denoised_shape, denoised_data) = vector_est_outputs["denoised_latent"].try_extract_tensor::<f32>()?;
-            xt = Array3::from_shape_vec(
-                (denoised_shape[0] as usize, denoised_shape[1] as usize, denoised_shape[2] as usize),
-                denoised_data.to_vec()
-            )?;
+            let steps = (step, total_step);
+            let session = self.vector_est_ort.get()?;
+            xt = match self.latent_tile {
+                Some((tile, overlap)) if xt.dim().2 > tile => {
+                    denoise_tiled(session, &xt, &text_emb, &style_ttl_value, &latent_mask, &text_mask, steps, tile, overlap)?
+                }
+                _ => run_vector_estimator(session, xt, &text_emb, &style_ttl_value, latent_mask.clone(), &text_mask, steps)?,
+            };
+            self.preview_step(step + 1, &xt, duration[0])?;
+            self.monitor_latent(step + 1, &xt, initial_norm);
         }
 
+        self.metrics.record_stage("denoise", stage_start.elapsed());
+
         // Generate waveform
-        let final_latent_value = Value::from_array(xt)?;
-        let vocoder_outputs = self.vocoder_ort.run(ort::inputs!{
-            "latent" => &final_latent_value
-        })?;
+        let stage_start = Instant::now();
+        let wav = match self.vocoder_split {
+            Some(split) if bsz > 1 => self.vocode_split(&xt, &latent_mask, split)?,
+            _ => run_vocoder(self.vocoder_ort.get()?, xt)?,
+        };
+        self.metrics.record_stage("vocoder", stage_start.elapsed());
 
-        let (_, wav_data) = vocoder_outputs["wav_tts"].try_extract_tensor::<f32>()?;
-        let wav: Vec<f32> = wav_data.to_vec();
+        let elapsed = infer_start.elapsed();
+        self.metrics.record_stage("total", elapsed);
+        let audio_secs: f32 = duration.iter().sum();
+        if audio_secs > 0.0 {
+            self.metrics.record_rtf(elapsed.as_secs_f32() / audio_secs);
+        }
 
-        Ok((wav, duration))
+        Ok((wav, output_duration))
     }
 
+    /// Synthesize one text (long texts are chunked) into a single waveform
+    ///
+    /// With `seed`, the initial noise is drawn from `StdRng::seed_from_u64(seed)`: the same
+    /// inputs and seed give bit-identical audio, however many inferences ran before. The
+    /// RNG and item seed set with `set_rng`/`set_seed`/`set_item_seed` are restored
+    /// afterwards, untouched by a seeded call. Without `seed` they are used as configured.
+    #[allow(clippy::too_many_arguments)]
     pub fn call(
         &mut self,
         text: &str,
@@ -687,34 +3796,240 @@ impl TextToSpeech {
         total_step: usize,
         speed: f32,
         silence_duration: f32,
+        initial_latent: Option<Array3<f32>>,
+        seed: Option<u64>,
+    ) -> Result<(Vec<f32>, f32)> {
+        let Some(seed) = seed else {
+            return self.call_unseeded(text, lang, style, total_step, speed, silence_duration, initial_latent);
+        };
+        let rng = self.rng.replace(Box::new(StdRng::seed_from_u64(seed)));
+        let item_seed = self.item_seed.take();
+        let result = self.call_unseeded(text, lang, style, total_step, speed, silence_duration, initial_latent);
+        self.rng = rng;
+        self.item_seed = item_seed;
+        result
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn call_unseeded(
+        &mut self,
+        text: &str,
+        lang: &str,
+        style: &Style,
+        total_step: usize,
+        speed: f32,
+        silence_duration: f32,
+        initial_latent: Option<Array3<f32>>,
     ) -> Result<(Vec<f32>, f32)> {
-        let max_len = if lang == "ko" { 120 } else { 300 };
-        let chunks = chunk_text(text, Some(max_len));
-        
         let mut wav_cat: Vec<f32> = Vec::new();
+        let dur_cat = self.synthesize_chunks(text, lang, style, total_step, speed, silence_duration, initial_latent, |audio| {
+            wav_cat.extend_from_slice(audio);
+            Ok(())
+        })?;
+
+        self.check_round_trip(text, &wav_cat);
+        Ok((wav_cat, dur_cat))
+    }
+
+    /// Like `call`, but write each chunk (and the silence before it) to `sink` as soon as
+    /// it is synthesized, so peak memory is one chunk rather than the whole text
+    ///
+    /// Returns the total duration in seconds. The transcribe hook is not run.
+    #[allow(clippy::too_many_arguments)]
+    pub fn call_to_sink<W: Write + Seek>(
+        &mut self,
+        text: &str,
+        lang: &str,
+        style: &Style,
+        total_step: usize,
+        speed: f32,
+        silence_duration: f32,
+        sink: &mut WavSink<W>,
+    ) -> Result<f32> {
+        self.synthesize_chunks(text, lang, style, total_step, speed, silence_duration, None, |audio| {
+            sink.write_chunk(audio)
+        })
+    }
+
+    /// Synthesize text chunks as they arrive on `rx`, yielding `AudioBuffer`s in order
+    ///
+    /// Ordering: audio follows the order chunks are received; blank chunks are skipped.
+    /// Each chunk goes through `call`, so long chunks are still split internally.
+    /// Adjacent chunks are joined with an equal-power crossfade of `opts.crossfade_secs`,
+    /// so the last `crossfade_secs` of audio is held back until the next chunk arrives.
+    /// Buffers are therefore not one-to-one with chunks: N chunks yield up to N + 1
+    /// buffers. A chunk whose audio fits within the crossfade yields nothing of its own
+    /// (it all joins the held-back tail), and the tail is flushed as one extra buffer
+    /// once every sender is dropped.
+    ///
+    /// Backpressure: synthesis runs on the thread calling `next`, which blocks in `recv`
+    /// until a chunk (or disconnection) arrives. Nothing is read ahead, so a producer using
+    /// `sync_channel(n)` blocks once `n` chunks are waiting; with `channel()` they queue
+    /// without bound. A synthesis error is yielded as `Err` and the stream continues.
+    pub fn synthesize_channel<'a>(
+        &'a mut self,
+        rx: Receiver<String>,
+        style: &'a Style,
+        opts: StreamOptions,
+    ) -> ChannelStream<'a> {
+        ChannelStream {
+            tts: self,
+            rx,
+            style,
+            opts,
+            tail: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// Split `text` into chunks, synthesize them in order and pass each piece of audio
+    /// (inter-chunk silence, then the trimmed chunk) to `emit`; returns the total duration
+    #[allow(clippy::too_many_arguments)]
+    fn synthesize_chunks(
+        &mut self,
+        text: &str,
+        lang: &str,
+        style: &Style,
+        total_step: usize,
+        speed: f32,
+        silence_duration: f32,
+        initial_latent: Option<Array3<f32>>,
+        mut emit: impl FnMut(&[f32]) -> Result<()>,
+    ) -> Result<f32> {
+        let max_len = if lang == "ko" { 120 } else { 300 };
+        let chunks = chunk_text_with_breaks(text, Some(max_len));
+        let pauses = self.chunk_pauses.unwrap_or(ChunkPauses::uniform(silence_duration));
+
+        if initial_latent.is_some() && chunks.len() > 1 {
+            bail!(
+                "An initial latent can only be applied to a single chunk, but the text was split into {} chunks",
+                chunks.len()
+            );
+        }
+        if self.dump_latent_path.is_some() && chunks.len() > 1 {
+            bail!(
+                "The latent can only be dumped for a single chunk, but the text was split into {} chunks",
+                chunks.len()
+            );
+        }
+        let mut initial_latent = initial_latent;
+
         let mut dur_cat: f32 = 0.0;
+        // The --max-wav-seconds cap applies to the whole text, not to each chunk
+        let max_samples = self.max_wav_seconds.map(|secs| (secs * self.sample_rate as f32) as usize);
+        let mut emitted = 0usize;
+
+        for (i, (chunk, _)) in chunks.iter().enumerate() {
+            let (wav, duration) = self._infer(std::slice::from_ref(chunk), &[lang.to_string()], style, total_step, speed, initial_latent.take())?;
 
-        for (i, chunk) in chunks.iter().enumerate() {
-            let (wav, duration) = self._infer(&[chunk.clone()], &[lang.to_string()], style, total_step, speed)?;
-            
             let dur = duration[0];
             let wav_len = (self.sample_rate as f32 * dur) as usize;
             let wav_chunk = &wav[..wav_len.min(wav.len())];
 
-            if i == 0 {
-                wav_cat.extend_from_slice(wav_chunk);
-                dur_cat = dur;
-            } else {
-                let silence_len = (silence_duration * self.sample_rate as f32) as usize;
-                let silence = vec![0.0f32; silence_len];
-                
-                wav_cat.extend_from_slice(&silence);
-                wav_cat.extend_from_slice(wav_chunk);
-                dur_cat += silence_duration + dur;
+            // The pause depends on how the previous chunk ended
+            let silence_duration = match i {
+                0 => 0.0,
+                _ => {
+                    let (prev_chunk, paragraph_end) = &chunks[i - 1];
+                    pauses.after(prev_chunk, *paragraph_end)
+                }
+            };
+            let silence = vec![0.0f32; (silence_duration * self.sample_rate as f32) as usize];
+
+            if let Some(max_samples) = max_samples {
+                let needed = emitted + silence.len() + wav_chunk.len();
+                if needed > max_samples {
+                    let max_seconds = max_samples as f32 / self.sample_rate as f32;
+                    if self.overlong_policy == OverlongPolicy::Error {
+                        bail!(
+                            "Output reaches {:.2}s at chunk {} of {}, more than the maximum of {:.2}s",
+                            needed as f32 / self.sample_rate as f32,
+                            i + 1,
+                            chunks.len(),
+                            max_seconds
+                        );
+                    }
+                    eprintln!(
+                        "Warning: output exceeds the maximum of {:.2}s at chunk {} of {}; truncating",
+                        max_seconds,
+                        i + 1,
+                        chunks.len()
+                    );
+                    let room = max_samples - emitted;
+                    emit(&silence[..room.min(silence.len())])?;
+                    emit(&wav_chunk[..room.saturating_sub(silence.len())])?;
+                    return Ok(max_seconds);
+                }
+            }
+
+            if i > 0 {
+                emit(&silence)?;
             }
+            emit(wav_chunk)?;
+            emitted += silence.len() + wav_chunk.len();
+            dur_cat += silence_duration + dur;
         }
 
-        Ok((wav_cat, dur_cat))
+        Ok(dur_cat)
+    }
+
+    /// Synthesize `text` and encode it as a complete in-memory WAV file
+    ///
+    /// The audio is resampled (linear) when `sample_rate` differs from the model's rate.
+    #[allow(clippy::too_many_arguments)]
+    pub fn synthesize_wav_bytes(
+        &mut self,
+        text: &str,
+        lang: &str,
+        style: &Style,
+        total_step: usize,
+        speed: f32,
+        sample_rate: u32,
+        bit_depth: u16,
+    ) -> Result<Vec<u8>> {
+        self.synthesize(text, lang, style, total_step, speed)?
+            .resampled(sample_rate)?
+            .to_wav_bytes(bit_depth)
+    }
+
+    /// Synthesize `text` into an `AudioBuffer` trimmed to the predicted duration
+    pub fn synthesize(
+        &mut self,
+        text: &str,
+        lang: &str,
+        style: &Style,
+        total_step: usize,
+        speed: f32,
+    ) -> Result<AudioBuffer> {
+        let (mut wav, duration) = self.call(text, lang, style, total_step, speed, 0.3, None, None)?;
+        wav.truncate((self.sample_rate as f32 * duration) as usize);
+        Ok(self.audio_buffer(wav))
+    }
+
+    /// Like `batch`, returning one `AudioBuffer` per item trimmed to its predicted duration
+    pub fn batch_audio(
+        &mut self,
+        text_list: &[String],
+        lang_list: &[String],
+        style: &Style,
+        total_step: usize,
+        speed: f32,
+    ) -> Result<Vec<AudioBuffer>> {
+        let (wav, duration) = self.batch(text_list, lang_list, style, total_step, speed, None)?;
+        let wav_len = wav.len() / text_list.len().max(1);
+        Ok(duration
+            .iter()
+            .enumerate()
+            .map(|(i, &dur)| {
+                let actual_len = ((self.sample_rate as f32 * dur) as usize).min(wav_len);
+                self.audio_buffer(wav[i * wav_len..i * wav_len + actual_len].to_vec())
+            })
+            .collect())
+    }
+
+    /// Wrap samples synthesized by this model as mono audio at its sample rate
+    pub fn audio_buffer(&self, samples: Vec<f32>) -> AudioBuffer {
+        AudioBuffer::mono(samples, self.sample_rate as u32)
     }
 
     pub fn batch(
@@ -724,8 +4039,121 @@ impl TextToSpeech {
         style: &Style,
         total_step: usize,
         speed: f32,
+        initial_latent: Option<Array3<f32>>,
     ) -> Result<(Vec<f32>, Vec<f32>)> {
-        self._infer(text_list, lang_list, style, total_step, speed)
+        let (wav, duration) = self._infer(text_list, lang_list, style, total_step, speed, initial_latent)?;
+        if self.transcribe_hook.is_some() {
+            let wav_len = wav.len() / text_list.len().max(1);
+            for (i, (text, &dur)) in text_list.iter().zip(duration.iter()).enumerate() {
+                let actual_len = ((self.sample_rate as f32 * dur) as usize).min(wav_len);
+                self.check_round_trip(text, &wav[i * wav_len..i * wav_len + actual_len]);
+            }
+        }
+        Ok((wav, duration))
+    }
+
+    /// Synthesize each batch item individually, collecting a result per item
+    ///
+    /// Successful items return their untruncated waveform and predicted duration.
+    pub fn batch_each(
+        &mut self,
+        text_list: &[String],
+        lang_list: &[String],
+        style: &Style,
+        total_step: usize,
+        speed: f32,
+    ) -> Vec<Result<(Vec<f32>, f32)>> {
+        text_list
+            .iter()
+            .zip(lang_list.iter())
+            .enumerate()
+            .map(|(i, (text, lang))| {
+                let item_style = style.select(i);
+                let (wav, duration) = self._infer(
+                    std::slice::from_ref(text),
+                    std::slice::from_ref(lang),
+                    &item_style,
+                    total_step,
+                    speed,
+                    None,
+                )?;
+                let actual_len = ((self.sample_rate as f32 * duration[0]) as usize).min(wav.len());
+                self.check_round_trip(text, &wav[..actual_len]);
+                Ok((wav, duration[0]))
+            })
+            .collect()
+    }
+}
+
+// ============================================================================
+// Streaming Synthesis
+// ============================================================================
+
+/// Per-stream settings for `TextToSpeech::synthesize_channel`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamOptions {
+    pub lang: String,
+    pub total_step: usize,
+    pub speed: f32,
+    /// Pause between sentences split out of one long chunk
+    pub silence_duration: f32,
+    /// Overlap between consecutive chunks
+    pub crossfade_secs: f32,
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        StreamOptions {
+            lang: "en".to_string(),
+            total_step: 5,
+            speed: 1.05,
+            silence_duration: 0.3,
+            crossfade_secs: 0.01,
+        }
+    }
+}
+
+/// Audio stream returned by `TextToSpeech::synthesize_channel`
+pub struct ChannelStream<'a> {
+    tts: &'a mut TextToSpeech,
+    rx: Receiver<String>,
+    style: &'a Style,
+    opts: StreamOptions,
+    /// End of the previous chunk, waiting to be crossfaded into the next
+    tail: Vec<f32>,
+    done: bool,
+}
+
+impl Iterator for ChannelStream<'_> {
+    type Item = Result<AudioBuffer>;
+
+    fn next(&mut self) -> Option<Result<AudioBuffer>> {
+        let sample_rate = self.tts.sample_rate as u32;
+        while !self.done {
+            let Ok(text) = self.rx.recv() else {
+                self.done = true;
+                break;
+            };
+            if text.trim().is_empty() {
+                continue;
+            }
+            let StreamOptions { lang, total_step, speed, silence_duration, .. } = &self.opts;
+            let wav = match self.tts.call(&text, lang, self.style, *total_step, *speed, *silence_duration, None, None) {
+                Ok((wav, _)) => wav,
+                Err(e) => return Some(Err(e)),
+            };
+            let tail = std::mem::take(&mut self.tail);
+            let mut joined = crossfade(&tail, &wav, tail.len());
+            let fade_len = (self.opts.crossfade_secs * sample_rate as f32) as usize;
+            self.tail = joined.split_off(joined.len().saturating_sub(fade_len));
+            if !joined.is_empty() {
+                return Some(Ok(AudioBuffer::mono(joined, sample_rate)));
+            }
+        }
+        if self.tail.is_empty() {
+            return None;
+        }
+        Some(Ok(AudioBuffer::mono(std::mem::take(&mut self.tail), sample_rate)))
     }
 }
 
@@ -738,10 +4166,7 @@ pub fn load_voice_style(voice_style_paths: &[String], verbose: bool) -> Result<S
     let bsz = voice_style_paths.len();
 
     // Read first file to get dimensions
-    let first_file = File::open(&voice_style_paths[0])
-        .context("Failed to open voice style file")?;
-    let first_reader = BufReader::new(first_file);
-    let first_data: VoiceStyleData = serde_json::from_reader(first_reader)?;
+    let first_data: VoiceStyleData = read_json_file(&voice_style_paths[0])?;
 
     let ttl_dims = &first_data.style_ttl.dims;
     let dp_dims = &first_data.style_dp.dims;
@@ -759,9 +4184,9 @@ pub fn load_voice_style(voice_style_paths: &[String], verbose: bool) -> Result<S
 
     // Fill in the data
     for (i, path) in voice_style_paths.iter().enumerate() {
-        let file = File::open(path).context("Failed to open voice style file")?;
-        let reader = BufReader::new(file);
-        let data: VoiceStyleData = serde_json::from_reader(reader)?;
+        let data: VoiceStyleData = read_json_file(path)?;
+        check_style_finite(path, "style_ttl", &data.style_ttl.data)?;
+        check_style_finite(path, "style_dp", &data.style_dp.data)?;
 
         // Flatten TTL data
         let ttl_offset = i * ttl_dim1 * ttl_dim2;
@@ -801,38 +4226,400 @@ pub fn load_voice_style(voice_style_paths: &[String], verbose: bool) -> Result<S
     })
 }
 
-/// Load TTS components
-pub fn load_text_to_speech(onnx_dir: &str, use_gpu: bool) -> Result<TextToSpeech> {
-    if use_gpu {
-        anyhow::bail!("GPU mode is not supported yet");
+/// Reject a style tensor holding NaN or infinity, naming the first bad element
+fn check_style_finite(path: &str, name: &str, data: &[Vec<Vec<f32>>]) -> Result<()> {
+    for (b, batch) in data.iter().enumerate() {
+        for (r, row) in batch.iter().enumerate() {
+            if let Some(c) = row.iter().position(|v| !v.is_finite()) {
+                bail!("{}: {}[{}][{}][{}] is {} (corrupt voice style export?)", path, name, b, r, c, row[c]);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Load voice styles one at a time instead of into one batch array up front
+///
+/// Each item is a single-voice `Style`; files are only read as the iterator advances,
+/// so peak memory stays at one style however many paths are given.
+pub fn iter_voice_styles(voice_style_paths: &[String]) -> impl Iterator<Item = Result<Style>> + '_ {
+    voice_style_paths
+        .iter()
+        .map(|path| load_voice_style(std::slice::from_ref(path), false))
+}
+
+/// Wall-clock time spent loading each component in `load_text_to_speech_timed`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoadTimings {
+    /// `tts.json`, plus reading the archive when loading a bundle
+    pub config: Duration,
+    pub duration_predictor: Duration,
+    pub text_encoder: Duration,
+    pub vector_estimator: Duration,
+    pub vocoder: Duration,
+    pub unicode_indexer: Duration,
+}
+
+impl LoadTimings {
+    pub fn total(&self) -> Duration {
+        self.config
+            + self.duration_predictor
+            + self.text_encoder
+            + self.vector_estimator
+            + self.vocoder
+            + self.unicode_indexer
+    }
+
+    /// Component names paired with their load time, in load order
+    pub fn entries(&self) -> [(&'static str, Duration); 6] {
+        [
+            ("tts.json", self.config),
+            ("duration_predictor.onnx", self.duration_predictor),
+            ("text_encoder.onnx", self.text_encoder),
+            ("vector_estimator.onnx", self.vector_estimator),
+            ("vocoder.onnx", self.vocoder),
+            ("unicode_indexer.json", self.unicode_indexer),
+        ]
+    }
+}
+
+/// Run `f` and add its wall-clock time to `slot`
+fn timed<T>(slot: &mut Duration, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    let start = Instant::now();
+    let result = f();
+    *slot += start.elapsed();
+    result
+}
+
+/// Where model files come from: a directory, or a tar bundle read into memory
+enum ModelSource {
+    Dir(PathBuf),
+    Bundle {
+        path: PathBuf,
+        files: HashMap<String, Vec<u8>>,
+    },
+}
+
+impl ModelSource {
+    /// Open `path` as a bundle if it is a file, otherwise as a model directory
+    fn open(path: &str) -> Result<Self> {
+        let path = PathBuf::from(path);
+        if !path.is_file() {
+            return Ok(ModelSource::Dir(path));
+        }
+
+        let file = File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut archive = tar::Archive::new(BufReader::new(file));
+        let mut files = HashMap::new();
+        for entry in archive
+            .entries()
+            .with_context(|| format!("{} is not a tar bundle", path.display()))?
+        {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            // Match on the file name alone so bundles may keep files in a top-level folder
+            let Some(name) = entry.path()?.file_name().map(|n| n.to_string_lossy().into_owned()) else {
+                continue;
+            };
+            let mut bytes = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut bytes)?;
+            files.insert(name, bytes);
+        }
+        Ok(ModelSource::Bundle { path, files })
     }
-    println!("Using CPU for inference\n");
 
-    let cfgs = load_cfgs(onnx_dir)?;
+    fn json<T: DeserializeOwned>(&self, name: &str) -> Result<T> {
+        match self {
+            ModelSource::Dir(dir) => read_json_file(dir.join(name)),
+            ModelSource::Bundle { path, files } => {
+                let bytes = files
+                    .get(name)
+                    .with_context(|| format!("Bundle {} has no {}", path.display(), name))?;
+                parse_json_slice(bytes, &format!("{}:{}", path.display(), name))
+            }
+        }
+    }
 
-    let dp_path = format!("{}/duration_predictor.onnx", onnx_dir);
-    let text_enc_path = format!("{}/text_encoder.onnx", onnx_dir);
-    let vector_est_path = format!("{}/vector_estimator.onnx", onnx_dir);
-    let vocoder_path = format!("{}/vocoder.onnx", onnx_dir);
+    /// Take the named model out of the source as a not-yet-loaded session
+    ///
+    /// With `profile_dir`, the session profiles into `<profile_dir>/<model name>_<timestamp>.json`.
+    fn session(&mut self, name: &str, profile_dir: Option<&Path>) -> Result<LazySession> {
+        let session = match self {
+            ModelSource::Dir(dir) => LazySession::new(dir.join(name)),
+            ModelSource::Bundle { path, files } => {
+                let bytes = files
+                    .remove(name)
+                    .with_context(|| format!("Bundle {} has no {}", path.display(), name))?;
+                LazySession::from_bytes(format!("{}:{}", path.display(), name), bytes)
+            }
+        };
+        Ok(match profile_dir {
+            Some(dir) => session.with_profiling(dir.join(name.trim_end_matches(".onnx"))),
+            None => session,
+        })
+    }
+}
 
-    let dp_ort = Session::builder()?
-        .commit_from_file(&dp_path)?;
-    let text_enc_ort = Session::builder()?
-        .commit_from_file(&text_enc_path)?;
-    let vector_est_ort = Session::builder()?
-        .commit_from_file(&vector_est_path)?;
-    let vocoder_ort = Session::builder()?
-        .commit_from_file(&vocoder_path)?;
+/// Load TTS components
+///
+/// `onnx_dir` is either a model directory or an uncompressed tar bundle holding the same
+/// files. Only the config and indexer are read here; each ONNX session is built on first use.
+/// With `profile_dir`, every session records an ONNX Runtime profile there (see
+/// `TextToSpeech::end_profiling`). Sessions run on `backend`, falling back to CPU with a
+/// warning where its provider cannot be registered.
+pub fn load_text_to_speech(onnx_dir: &str, backend: Backend, profile_dir: Option<&Path>) -> Result<TextToSpeech> {
+    println!("Using {} for inference\n", backend);
 
-    let unicode_indexer_path = format!("{}/unicode_indexer.json", onnx_dir);
-    let text_processor = UnicodeProcessor::new(&unicode_indexer_path)?;
+    let mut source = ModelSource::open(onnx_dir)?;
+    let cfgs: Config = source.json("tts.json")?;
+    let text_processor = UnicodeProcessor::from_indexer(source.json("unicode_indexer.json")?);
 
     Ok(TextToSpeech::new(
+        cfgs,
+        text_processor,
+        source.session("duration_predictor.onnx", profile_dir)?.with_backend(backend),
+        source.session("text_encoder.onnx", profile_dir)?.with_backend(backend),
+        source.session("vector_estimator.onnx", profile_dir)?.with_backend(backend),
+        source.session("vocoder.onnx", profile_dir)?.with_backend(backend),
+    ))
+}
+
+/// Load TTS components eagerly, also reporting how long each file took to load
+pub fn load_text_to_speech_timed(
+    onnx_dir: &str,
+    backend: Backend,
+    profile_dir: Option<&Path>,
+) -> Result<(TextToSpeech, LoadTimings)> {
+    println!("Using {} for inference\n", backend);
+
+    let mut timings = LoadTimings::default();
+    let (mut source, cfgs) = timed(&mut timings.config, || {
+        let source = ModelSource::open(onnx_dir)?;
+        let cfgs: Config = source.json("tts.json")?;
+        Ok((source, cfgs))
+    })?;
+
+    let mut dp_ort = source.session("duration_predictor.onnx", profile_dir)?.with_backend(backend);
+    let mut text_enc_ort = source.session("text_encoder.onnx", profile_dir)?.with_backend(backend);
+    let mut vector_est_ort = source.session("vector_estimator.onnx", profile_dir)?.with_backend(backend);
+    let mut vocoder_ort = source.session("vocoder.onnx", profile_dir)?.with_backend(backend);
+
+    timed(&mut timings.duration_predictor, || dp_ort.get().map(drop))?;
+    timed(&mut timings.text_encoder, || text_enc_ort.get().map(drop))?;
+    timed(&mut timings.vector_estimator, || vector_est_ort.get().map(drop))?;
+    timed(&mut timings.vocoder, || vocoder_ort.get().map(drop))?;
+
+    let text_processor = timed(&mut timings.unicode_indexer, || {
+        Ok(UnicodeProcessor::from_indexer(source.json("unicode_indexer.json")?))
+    })?;
+
+    let tts = TextToSpeech::new(
         cfgs,
         text_processor,
         dp_ort,
         text_enc_ort,
         vector_est_ort,
         vocoder_ort,
-    ))
+    );
+    Ok((tts, timings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mean and variance of `n` draws from `sample`
+    fn moments(n: usize, mut sample: impl FnMut() -> f32) -> (f64, f64) {
+        let draws: Vec<f64> = (0..n).map(|_| sample() as f64).collect();
+        let mean = draws.iter().sum::<f64>() / n as f64;
+        let var = draws.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n as f64;
+        (mean, var)
+    }
+
+    #[test]
+    fn checked_mask_rejects_lengths_beyond_max_len() {
+        assert!(length_to_mask_checked(&[2, 5], Some(3)).is_err());
+        assert_eq!(length_to_mask_checked(&[2, 3], Some(3)).unwrap(), length_to_mask(&[2, 3], Some(3)));
+        // Without a max_len every length fits
+        assert_eq!(length_to_mask_checked(&[0, 5], None).unwrap().shape(), &[2, 1, 5]);
+    }
+
+    /// Noise for two items of 0.5 s and 1 s at a 1 kHz rate, 100-sample chunks
+    fn noise(rng: Option<&mut dyn RngCore>) -> (Array3<f32>, Array3<f32>) {
+        sample_noisy_latent(&[0.5, 1.0], 1000, 50, 2, 4, LatentRounding::Ceil, 64, rng).unwrap()
+    }
+
+    #[test]
+    fn noisy_latent_is_masked() {
+        let (latent, mask) = noise(None);
+        assert_eq!(latent.shape(), &[2, 8, 10]);
+        assert_eq!(mask.shape(), &[2, 1, 10]);
+        assert!(latent.slice(s![0, .., 5..]).iter().all(|&v| v == 0.0));
+        assert!(latent.slice(s![1, .., ..]).iter().all(|&v| v != 0.0));
+    }
+
+    #[test]
+    fn noisy_latent_is_seeded_or_fresh() {
+        assert_ne!(noise(None).0, noise(None).0);
+        let seeded = |seed| noise(Some(&mut StdRng::seed_from_u64(seed))).0;
+        assert_eq!(seeded(1), seeded(1));
+        assert_ne!(seeded(1), seeded(2));
+    }
+
+    #[test]
+    fn per_item_noise_depends_only_on_seed_and_index() {
+        let per_item = |duration: &[f32], seed| {
+            sample_noisy_latent_per_item(duration, 1000, 50, 2, 4, LatentRounding::Ceil, 64, seed).unwrap().0
+        };
+        let pair = per_item(&[1.0, 1.0], 9);
+        assert_eq!(pair, per_item(&[1.0, 1.0], 9));
+        assert_ne!(pair, per_item(&[1.0, 1.0], 10));
+        assert_ne!(pair.slice(s![0, .., ..]), pair.slice(s![1, .., ..]));
+        // Item 0 is unaffected by the rest of the batch
+        let single = per_item(&[1.0], 9);
+        assert_eq!(single.slice(s![0, .., ..]), pair.slice(s![0, .., ..]));
+    }
+
+    #[test]
+    fn per_item_noise_ignores_batch_padding() {
+        let per_item = |duration: &[f32]| {
+            sample_noisy_latent_per_item(duration, 1000, 50, 2, 4, LatentRounding::Ceil, 64, 9).unwrap().0
+        };
+        // Item 0 is 5 frames long, alone and padded to the 10 frames of item 1
+        let single = per_item(&[0.5]);
+        let mixed = per_item(&[0.5, 1.0]);
+        assert_eq!(single.shape(), &[1, 8, 5]);
+        assert_eq!(single.slice(s![0, .., ..]), mixed.slice(s![0, .., ..5]));
+        assert!(mixed.slice(s![0, .., 5..]).iter().all(|&v| v == 0.0));
+        // The longest item is unaffected as well
+        let swapped = per_item(&[1.0, 0.5]);
+        assert_eq!(swapped.slice(s![0, .., ..]), per_item(&[1.0]).slice(s![0, .., ..]));
+    }
+
+    fn normalize_en(text: &str) -> String {
+        EnglishNormalizer.normalize(text, "en")
+    }
+
+    #[test]
+    fn grouped_numbers_need_full_groups() {
+        assert_eq!(normalize_en("1,200 people"), "one thousand two hundred people");
+        assert_eq!(normalize_en("1,2,3"), "one,two,three");
+        assert_eq!(normalize_en("in 1990,2000"), "in one thousand nine hundred ninety,two thousand");
+        assert_eq!(normalize_en("12,3456"), "twelve,three thousand four hundred fifty-six");
+        assert_eq!(normalize_en("count 1, 2, 3."), "count one, two, three.");
+        assert_eq!(normalize_en("pi is 3.14"), "pi is three point one four");
+    }
+
+    #[test]
+    fn currency_and_percent_use_grouped_numbers() {
+        assert_eq!(
+            normalize_en("$1,234.50"),
+            "one thousand two hundred thirty-four dollars and fifty cents"
+        );
+        assert_eq!(normalize_en("£1,5"), "one pound,five");
+        assert_eq!(normalize_en("12,500% and 5%,10%"), "twelve thousand five hundred percent and five percent,ten percent");
+    }
+
+    #[test]
+    fn ms_is_not_expanded_to_miss() {
+        assert_eq!(normalize_en("Ms. Smith met Mrs. Jones"), "Mizz Smith met Missus Jones");
+    }
+
+    #[test]
+    fn astral_characters_map_to_out_of_range_id() {
+        // A BMP-sized table: every codepoint below U+10000 has an entry
+        let mut processor = UnicodeProcessor::from_indexer((0..0x10000).map(|c| c % 100).collect());
+        let emoji = '\u{1F600}';
+        assert_eq!(processor.map_value(emoji as usize), -1);
+        assert!(!processor.contains(emoji));
+
+        processor.set_out_of_range_id(Some(3));
+        assert_eq!(processor.map_value(emoji as usize), 3);
+        assert!(processor.contains(emoji));
+        // Characters inside the table keep their own entry
+        assert_eq!(processor.map_value('a' as usize), 'a' as i64 % 100);
+
+        processor.set_out_of_range_id(None);
+        assert_eq!(processor.map_value(emoji as usize), -1);
+    }
+
+    #[test]
+    fn script_skips_lines_without_text() {
+        let lines = parse_script("[voice:M1] Hello.\n\n[voice:F1]   \nNo tag [here].\n[voice:] Empty id.");
+        assert_eq!(
+            lines,
+            vec![
+                ScriptLine { voice: Some("M1".to_string()), text: "Hello.".to_string() },
+                ScriptLine { voice: None, text: "No tag [here].".to_string() },
+                ScriptLine { voice: None, text: "[voice:] Empty id.".to_string() },
+            ]
+        );
+    }
+
+    #[test]
+    fn limiter_holds_the_ceiling_around_isolated_peaks() {
+        let mut audio = vec![0.5f32; 4000];
+        audio[2000] = 2.0;
+        audio[2001] = -1.5;
+        limit_peaks(&mut audio, 1.0);
+        assert_eq!(audio[2000], 1.0);
+        assert!(audio.iter().all(|x| x.abs() <= 1.0));
+        // Untouched beyond the look-ahead before and the release after
+        assert!(audio[..2000 - LIMIT_LOOKAHEAD].iter().all(|&x| x == 0.5));
+        assert!(audio[2001 + LIMIT_RELEASE..].iter().all(|&x| x == 0.5));
+        assert!(audio[1999] < 0.5 && audio[2002] < 0.5);
+    }
+
+    #[test]
+    fn limiter_handles_long_overshoots() {
+        // Every sample over the ceiling by the same amount: each one ends up exactly at it
+        let mut audio: Vec<f32> = (0..1_000_000).map(|i| if i % 2 == 0 { 2.0 } else { -2.0 }).collect();
+        limit_peaks(&mut audio, 1.0);
+        assert!(audio.iter().all(|x| x.abs() == 1.0));
+    }
+
+    #[test]
+    fn zero_total_step_is_rejected() {
+        let err = check_total_step(0).unwrap_err();
+        assert!(err.to_string().contains("total_step must be at least 1"), "{err}");
+        assert!(check_total_step(1).is_ok());
+    }
+
+    /// Needs the released models under `assets/`: `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn seeded_call_is_bit_identical() -> Result<()> {
+        let mut tts = load_text_to_speech("assets/onnx", Backend::Cpu, None)?;
+        let style = load_voice_style(&["assets/voice_styles/M1.json".to_string()], false)?;
+        let mut run = |seed| tts.call("Same seed, same audio.", "en", &style, 2, 1.05, 0.3, None, seed);
+        let (first, _) = run(Some(11))?;
+        let (unseeded, _) = run(None)?;
+        let (second, _) = run(Some(11))?;
+        assert_eq!(first, second);
+        assert_ne!(first, unseeded);
+        Ok(())
+    }
+
+    #[test]
+    fn box_muller_is_standard_normal() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut normal = BoxMuller::default();
+        let (mean, var) = moments(200_000, || normal.sample(&mut rng));
+        // Standard errors are ~0.002 for the mean and ~0.003 for the variance
+        assert!(mean.abs() < 0.01, "mean {mean}");
+        assert!((var - 1.0).abs() < 0.02, "variance {var}");
+    }
+
+    #[test]
+    fn box_muller_is_deterministic_per_seed() {
+        let draw = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut normal = BoxMuller::default();
+            (0..16).map(|_| normal.sample(&mut rng)).collect::<Vec<_>>()
+        };
+        assert_eq!(draw(7), draw(7));
+        assert_ne!(draw(7), draw(8));
+    }
 }
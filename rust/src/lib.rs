@@ -0,0 +1,36 @@
+//! Supertonic text-to-speech with ONNX Runtime
+//!
+//! The items re-exported here are the stable surface; `helper` holds the full toolkit
+//! the example binary is built from. Both need the default `runtime` feature; with
+//! `--no-default-features` only the dependency-light `math` module is built.
+#![cfg_attr(
+    feature = "runtime",
+    doc = r#"
+Load the models once with [`load_text_to_speech`], pick a voice with
+[`load_voice_style`], then call [`TextToSpeech::synthesize`] (one text, long texts
+are chunked) or [`TextToSpeech::batch_audio`] as often as needed. Both return
+[`helper::AudioBuffer`]s that carry their sample rate; [`TextToSpeech::call`] and
+[`TextToSpeech::batch`] give the raw samples and predicted durations instead.
+
+```no_run
+use supertonic::prelude::*;
+
+# fn main() -> anyhow::Result<()> {
+let mut text_to_speech = load_text_to_speech("assets/onnx", Backend::Cpu, None)?;
+let style = load_voice_style(&["assets/voice_styles/M1.json".to_string()], false)?;
+let audio = text_to_speech.synthesize("Hello there.", "en", &style, 5, 1.05)?;
+write_wav_file("hello.wav", &audio)?;
+# Ok(())
+# }
+```
+"#
+)]
+
+#[cfg(feature = "runtime")]
+pub mod helper;
+pub mod math;
+
+#[cfg(feature = "runtime")]
+pub use helper::{
+    load_text_to_speech, load_voice_style, prelude, write_wav_file, Config, Style, TextToSpeech, UnicodeProcessor,
+};
@@ -0,0 +1,2173 @@
+use anyhow::{Context, Result};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use ndarray::Array3;
+use ndarray_npy::read_npy;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::fs;
+use std::io::Write;
+use std::mem;
+
+use supertonic::helper::{
+    check_total_step, load_text_to_speech_timed, load_indexer_patch, read_json_file, load_voice_style, concat_styles, parse_script, timer, write_wav_file, write_wav_file_with_options, write_bwf_file, write_multichannel_wav, sanitize_filename,
+    render_name_template, content_hash, tpdf_dither, make_loop, onset_fade, base64_encode, pad_silence, mix_tracks, highpass, limit_peaks, apply_gain, compress, estimate_wav_bytes, match_level, read_wav_file, write_raw_pcm, audio_stats, silence_bounds, AudioBuffer, AudioStats, Backend, BandSpec, BextInfo, Equalizer, ChunkPauses, LatentRounding, Endian, LevelMeasure, OverlongPolicy, PcmOptions, PrometheusFileSink, PcmRounding, PcmScaling, SpeakerEmbedding, StreamOptions, Style, WavSink, TextToSpeech, UnmappableTextPolicy, VoiceLibrary,
+    EnglishNormalizer, NoopNormalizer, DEFAULT_MAX_LATENT_LEN,
+};
+
+#[derive(Parser, Debug)]
+#[command(name = "TTS ONNX Inference")]
+#[command(about = "TTS Inference with ONNX Runtime (Rust)", long_about = None)]
+struct Args {
+    /// Use GPU for inference (default: CPU); same as --backend cuda
+    #[arg(long, default_value = "false")]
+    use_gpu: bool,
+
+    /// Execution provider for all four models
+    #[arg(long, default_value = "cpu", value_parser = ["cpu", "coreml", "cuda"])]
+    backend: String,
+
+    /// Device ordinal for the execution provider, i.e. the CUDA device with --backend cuda
+    /// (with CUDA, falls back to $SUPERTONIC_DEVICE, then 0)
+    #[arg(long, visible_alias = "gpu-device-id")]
+    device: Option<u32>,
+
+    /// Path to ONNX model directory
+    #[arg(long, default_value = "assets/onnx")]
+    onnx_dir: String,
+
+    /// Number of denoising steps (overrides --quality)
+    #[arg(long)]
+    total_step: Option<usize>,
+
+    /// Quality preset used when --total-step is not given: draft (2 steps), normal (5), high (15)
+    #[arg(long, value_parser = ["draft", "normal", "high"], default_value = "normal")]
+    quality: String,
+
+    /// Speech speed factor (higher = faster)
+    #[arg(long, default_value = "1.05")]
+    speed: f32,
+
+    /// Number of times to generate
+    #[arg(long, default_value = "4")]
+    n_test: usize,
+
+    /// Voice style file path(s)
+    #[arg(long, default_values_t = vec!["assets/voice_styles/M1.json".to_string()])]
+    voice_style: Vec<String>,
+
+    /// Text(s) to synthesize
+    #[arg(long, default_values_t = vec!["This morning, I took a walk in the park, and the sound of the birds and the breeze was so pleasant that I stopped for a long time just to listen.".to_string()])]
+    text: Vec<String>,
+
+    /// Separator between texts in --text (empty string disables splitting)
+    #[arg(long, default_value = "|")]
+    text_delimiter: String,
+
+    /// Voice id(s) from the --voice-library index, used instead of --voice-style paths
+    #[arg(long, value_delimiter = ',')]
+    voice_style_id: Vec<String>,
+
+    /// Voice library index mapping voice ids to style files (used by --voice-style-id)
+    #[arg(long, default_value = "assets/voice_styles/index.json")]
+    voice_library: PathBuf,
+
+    /// Print the voices of --voice-library and exit
+    #[arg(long)]
+    list_voices: bool,
+
+    /// Blend all --voice-style files into one voice with these weights (one per style, summing to 1)
+    #[arg(long, value_delimiter = ',')]
+    mix_weights: Vec<f32>,
+
+    /// Speaker embedding JSON file(s) replacing the voice styles, one per item or one for all;
+    /// --voice-style still provides the expected style shapes
+    #[arg(long, value_delimiter = ',')]
+    speaker_embedding: Vec<PathBuf>,
+
+    /// Separator between paths in --voice-style (empty string disables splitting)
+    #[arg(long, default_value = ",")]
+    voice_delimiter: String,
+
+    /// Language(s) for synthesis (en, ko, es, pt, fr)
+    #[arg(long, value_delimiter = ',', default_values_t = vec!["en".to_string()])]
+    lang: Vec<String>,
+
+    /// Output directory
+    #[arg(long, default_value = "results")]
+    save_dir: String,
+
+    /// Enable batch mode (multiple text-style pairs)
+    #[arg(long, default_value = "false")]
+    batch: bool,
+
+    /// Zero-pad every batch output to the longest utterance's length
+    #[arg(long, default_value = "false")]
+    pad_to_max: bool,
+
+    /// Write the sampled noisy latent (before denoising) to this .npy file
+    #[arg(long)]
+    dump_latent: Option<PathBuf>,
+
+    /// Use the latent in this .npy file instead of sampling noise
+    #[arg(long)]
+    init_latent: Option<PathBuf>,
+
+    /// Synthesize batch items individually and keep going when one fails
+    #[arg(long, default_value = "false")]
+    continue_on_error: bool,
+
+    /// Expand numbers, currency and abbreviations into words (English only)
+    #[arg(long, default_value = "false")]
+    normalize_text: bool,
+
+    /// Skip NFKD and symbol cleanup for text that is already normalized
+    #[arg(long, default_value = "false")]
+    skip_normalization: bool,
+
+    /// Output file name template (without extension); placeholders:
+    /// {text}, {index}, {run}, {voice}, {seed}, {timestamp}
+    #[arg(long, default_value = "{text}_{run}")]
+    name_template: String,
+
+    /// Seed for the initial noise, making runs reproducible (default: random)
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Synthesize the first text twice with --seed, check the WAV bytes are identical and exit
+    #[arg(long, default_value = "false")]
+    check_determinism: bool,
+
+    /// Seed each batch item independently from --seed and its index instead of one shared stream
+    #[arg(long, default_value = "false")]
+    seed_per_item: bool,
+
+    /// Synthesize raw text ids from a JSON array of id arrays, skipping normalization and mapping
+    #[arg(long)]
+    ids_file: Option<PathBuf>,
+
+    /// Screenplay-style text file: one utterance per line, each optionally prefixed with
+    /// [voice:ID] (resolved against --voice-library); untagged lines use --voice-style
+    #[arg(long)]
+    script: Option<PathBuf>,
+
+    /// Synthesize this sentence once with every voice style in --voices-dir
+    #[arg(long)]
+    audition: Option<String>,
+
+    /// Directory of voice style JSON files used by --audition
+    #[arg(long, default_value = "assets/voice_styles")]
+    voices_dir: PathBuf,
+
+    /// Append every synthesis request (inputs, seed and synthesis settings) to this JSONL file
+    #[arg(long)]
+    request_log: Option<PathBuf>,
+
+    /// Re-run the requests recorded in a --request-log file instead of --text
+    #[arg(long)]
+    replay: Option<PathBuf>,
+
+    /// Synthesize the first text at each of these step counts with a shared seed
+    #[arg(long, value_delimiter = ',')]
+    sweep_steps: Vec<usize>,
+
+    /// Fail instead of warning when a text has no characters the model knows
+    #[arg(long, default_value = "false")]
+    strict_text: bool,
+
+    /// Make each output loop seamlessly and repeat it N times
+    #[arg(long = "loop", value_name = "N")]
+    r#loop: Option<usize>,
+
+    /// Crossfade length at the loop seam in milliseconds
+    #[arg(long, default_value = "50")]
+    loop_crossfade_ms: f32,
+
+    /// Experimental: scale style vectors as "ttl,dp" (one value scales both)
+    #[arg(long, value_delimiter = ',', num_args = 1..=2)]
+    style_scale: Vec<f32>,
+
+    /// Write a spectrogram PNG next to each WAV (requires the `spectrogram` feature)
+    #[arg(long, default_value = "false")]
+    spectrogram: bool,
+
+    /// Also write headerless f32 PCM (<name>.raw) next to each WAV
+    #[arg(long, default_value = "false")]
+    raw_output: bool,
+
+    /// Byte order of --raw-output samples
+    #[arg(long, value_parser = ["little", "big"], default_value = "little")]
+    raw_endian: String,
+
+    /// JSON object mapping characters (or "U+XXXX") to ids, overlaid on the unicode indexer
+    #[arg(long)]
+    indexer_patch: Option<PathBuf>,
+
+    /// Map characters beyond the end of the unicode indexer (e.g. astral-plane emoji) to this id
+    #[arg(long, allow_negative_numbers = true)]
+    out_of_range_id: Option<i64>,
+
+    /// Print the normalized text, codepoints and mapped ids of each input
+    #[arg(long, default_value = "false")]
+    dump_ids: bool,
+
+    /// Output audio format: wav, or opus (requires the `opus` feature)
+    #[arg(long, value_parser = ["wav", "opus"], default_value = "wav")]
+    output_format: String,
+
+    /// Opus bitrate in bits per second
+    #[arg(long, default_value = "32000")]
+    opus_bitrate: i32,
+
+    /// Silence to prepend to each output in milliseconds
+    #[arg(long, default_value = "0")]
+    pad_start_ms: f32,
+
+    /// Silence to append to each output in milliseconds
+    #[arg(long, default_value = "0")]
+    pad_end_ms: f32,
+
+    /// Mix all batch items into one track with these linear gains (one per item, or one for all)
+    #[arg(long, value_delimiter = ',')]
+    mix: Vec<f32>,
+
+    /// Level in dBFS at or below which --verbose/--metadata count head and tail samples as silent
+    #[arg(long, default_value = "-60", allow_negative_numbers = true)]
+    silence_threshold: f32,
+
+    /// Per-item output gain, linear (0.5) or in dB with a suffix (-6dB); one per item, or one for all
+    #[arg(long, value_delimiter = ',', value_parser = parse_gain, allow_hyphen_values = true)]
+    gains: Vec<f32>,
+
+    /// Start offsets in seconds for each mixed item (one per item, or one for all)
+    #[arg(long, value_delimiter = ',')]
+    mix_offsets: Vec<f32>,
+
+    /// Load the model set, run a short test synthesis, report PASS/FAIL and exit without writing files
+    #[arg(long, default_value = "false")]
+    validate_only: bool,
+
+    /// Name outputs by a hash of text, language, voice, steps, speed and seed, skipping existing files
+    #[arg(long, default_value = "false")]
+    content_hash_names: bool,
+
+    /// With --content-hash-names, synthesize and rewrite outputs that already exist
+    #[arg(long, default_value = "false")]
+    overwrite: bool,
+
+    /// Print how long each model file took to load
+    #[arg(long, default_value = "false")]
+    load_timings: bool,
+
+    /// Write an ONNX Runtime per-op profile (JSON) for each session into this directory
+    #[arg(long)]
+    profile_dir: Option<PathBuf>,
+
+    /// Batch mode: synthesize at most this many items at once to bound peak memory
+    #[arg(long)]
+    max_batch: Option<usize>,
+
+    /// Add TPDF dither before 16-bit WAV quantization
+    #[arg(long, default_value = "false")]
+    dither: bool,
+
+    /// Seed for --dither noise so output files are reproducible (default: random)
+    #[arg(long)]
+    dither_seed: Option<u64>,
+
+    /// 16-bit WAV full scale: 32767 (symmetric) or 32768 (full range, clamped)
+    #[arg(long, value_parser = ["32767", "32768"], default_value = "32767")]
+    pcm_scale: String,
+
+    /// How scaled WAV samples become integers: truncate (toward zero) or nearest
+    #[arg(long, value_parser = ["truncate", "nearest"], default_value = "truncate")]
+    pcm_rounding: String,
+
+    /// Batch mode: write all items into one WAV with one channel per item
+    #[arg(long, default_value = "false")]
+    multichannel: bool,
+
+    /// Batch mode: also write each item's full, untruncated vocoder output to `<name>_full.wav`
+    #[arg(long, default_value = "false")]
+    save_untruncated: bool,
+
+    /// Non-batch mode: pause in ms between long-text chunks by how each ends,
+    /// e.g. period=300,comma=150,newline=500 (default: 300 ms after every chunk)
+    #[arg(long)]
+    chunk_pauses: Option<String>,
+
+    /// Override the config's chunk_compress_factor (experiments only; degrades output)
+    #[arg(long)]
+    chunk_compress: Option<i32>,
+
+    /// Override the config's latent_dim to match a custom export; checked against the vector estimator
+    #[arg(long)]
+    latent_dim: Option<i32>,
+
+    /// Rounding of the latent length for partial chunks: ceil (released models), floor or round
+    #[arg(long, value_parser = ["ceil", "floor", "round"], default_value = "ceil")]
+    latent_rounding: String,
+
+    /// Refuse inferences whose latent would exceed this many frames (default 16384)
+    #[arg(long)]
+    max_latent_len: Option<usize>,
+
+    /// Cap each output at this many seconds (protects batch jobs from runaway durations)
+    #[arg(long)]
+    max_wav_seconds: Option<f32>,
+
+    /// Batch mode: vocode in sub-batches of this many items, each cut to its own length
+    #[arg(long)]
+    vocoder_split: Option<usize>,
+
+    /// Vocoder sessions running --vocoder-split sub-batches concurrently (each loads the vocoder)
+    #[arg(long, default_value = "1")]
+    vocoder_threads: usize,
+
+    /// Run the duration predictor and text encoder one after the other instead of concurrently
+    #[arg(long, default_value = "false")]
+    sequential_encode: bool,
+
+    /// When a predicted duration exceeds --max-wav-seconds: truncate it to the cap (with a warning) or error
+    #[arg(long, value_parser = ["truncate", "error"], default_value = "truncate")]
+    on_overlong: String,
+
+    /// Treat model consistency warnings (e.g. config vs vocoder sample rate) as errors
+    #[arg(long, default_value = "false")]
+    strict: bool,
+
+    /// Batch mode: require exactly one voice style and language per text instead of
+    /// broadcasting a single one to all texts
+    #[arg(long, default_value = "false")]
+    strict_batch: bool,
+
+    /// Vocode the latent after every denoising step and write trajectory/step_<k>.wav (slow)
+    #[arg(long, default_value = "false")]
+    save_trajectory: bool,
+
+    /// Write each output into a subdirectory of --save-dir named after its voice style
+    #[arg(long, default_value = "false")]
+    group_by_voice: bool,
+
+    /// Print extra per-output details such as level statistics
+    #[arg(long, default_value = "false")]
+    verbose: bool,
+
+    /// Run only the vocoder on this latent .npy (e.g. from --dump-latent) and save the audio
+    #[arg(long)]
+    vocode_only: Option<PathBuf>,
+
+    /// Apply a feed-forward dynamic range compressor to each output (after --gains, before --match-loudness)
+    #[arg(long, default_value = "false")]
+    compress: bool,
+
+    /// --compress threshold in dBFS
+    #[arg(long, default_value = "-18.0", allow_negative_numbers = true)]
+    compress_threshold: f32,
+
+    /// --compress ratio: level above the threshold rises 1/ratio as fast
+    #[arg(long, default_value = "3.0")]
+    compress_ratio: f32,
+
+    /// --compress attack time in milliseconds
+    #[arg(long, default_value = "5.0")]
+    compress_attack_ms: f32,
+
+    /// --compress release time in milliseconds
+    #[arg(long, default_value = "80.0")]
+    compress_release_ms: f32,
+
+    /// Scale each output so its level (see --match-measure) matches this reference WAV
+    #[arg(long)]
+    match_loudness: Option<PathBuf>,
+
+    /// Level compared by --match-loudness
+    #[arg(long, default_value = "rms", value_parser = ["rms", "peak"])]
+    match_measure: String,
+
+    /// Denoise latents longer than this many frames in overlapping time tiles to bound memory
+    /// (only for locally-attending models; others produce seams)
+    #[arg(long)]
+    latent_tile: Option<usize>,
+
+    /// Frames shared (and crossfaded) by adjacent --latent-tile tiles
+    #[arg(long, default_value = "16")]
+    latent_tile_overlap: usize,
+
+    /// Log the latent's L2 norm and max abs value after each denoising step
+    #[arg(long, default_value = "false")]
+    monitor_latent: bool,
+
+    /// With --monitor-latent, warn when the latent norm exceeds this multiple of the initial noise norm
+    #[arg(long, default_value = "10.0")]
+    latent_norm_limit: f32,
+
+    /// Read text from stdin line by line and synthesize each line as it arrives into
+    /// <save-dir>/stream.wav, using the first voice style and language
+    #[arg(long, default_value = "false")]
+    stream_stdin: bool,
+
+    /// Crossfade between consecutive --stream-stdin lines in milliseconds
+    #[arg(long, default_value = "10.0")]
+    stream_crossfade_ms: f32,
+
+    /// Predict durations only and print the planned output files with their estimated WAV sizes
+    /// (and the total) as JSON, without synthesizing
+    #[arg(long, default_value = "false")]
+    dry_run: bool,
+
+    /// Print the validated config with overrides applied (e.g. --chunk-compress) as JSON and exit
+    #[arg(long, default_value = "false")]
+    print_config: bool,
+
+    /// Write a JSON sidecar (same name, .json) with the inputs and level statistics of each output
+    #[arg(long, default_value = "false")]
+    metadata: bool,
+
+    /// Include the WAV file base64-encoded as "audio_base64" in the --metadata sidecar
+    #[arg(long, default_value = "false")]
+    embed_audio: bool,
+
+    /// Longest output (seconds) that --embed-audio inlines; longer ones are left out with a warning
+    #[arg(long, default_value = "10.0")]
+    embed_audio_max_sec: f32,
+
+    /// Write stage latencies and real-time factor to this file in Prometheus text format
+    #[arg(long)]
+    metrics_file: Option<PathBuf>,
+
+    /// What to do when an output has zero samples: skip it, or write 10 ms of silence
+    #[arg(long, value_parser = ["skip", "silence"], default_value = "skip")]
+    empty_audio: String,
+
+    /// Length in ms of the fade-in applied to the start of every utterance against onset pops
+    #[arg(long, default_value = "2.0")]
+    onset_fade_ms: f32,
+
+    /// Disable the onset fade-in
+    #[arg(long, default_value = "false")]
+    no_onset_fade: bool,
+
+    /// Apply a 12 dB/octave high-pass filter at this cutoff frequency (Hz)
+    #[arg(long)]
+    highpass: Option<f32>,
+
+    /// Embed a Broadcast Wave `bext` chunk with this description (up to 256 ASCII characters)
+    #[arg(long)]
+    bwf_description: Option<String>,
+
+    /// BWF originator, e.g. the producing organisation or tool (up to 32 characters)
+    #[arg(long)]
+    bwf_originator: Option<String>,
+
+    /// BWF originator reference, a unique id for the file (up to 32 characters)
+    #[arg(long)]
+    bwf_originator_ref: Option<String>,
+
+    /// Peaking EQ bands as freq:gain_db:q, comma-separated (e.g. 120:-3:0.7,3500:2.5:1.2)
+    #[arg(long, value_delimiter = ',', value_parser = parse_eq_band, allow_hyphen_values = true)]
+    eq: Vec<BandSpec>,
+
+    /// Brickwall-limit peaks to this level in dBFS (e.g. -1.0), attenuating only around overshoots
+    #[arg(long, allow_negative_numbers = true)]
+    limit: Option<f32>,
+}
+
+/// Split each argument occurrence on `delimiter`; an empty delimiter keeps values whole
+fn split_values(values: &[String], delimiter: &str) -> Vec<String> {
+    if delimiter.is_empty() {
+        return values.to_vec();
+    }
+    values
+        .iter()
+        .flat_map(|v| v.split(delimiter).map(|s| s.to_string()))
+        .collect()
+}
+
+/// Cut the synthesized waveform into per-item outputs truncated to each predicted duration
+fn split_outputs(
+    wav: &[f32],
+    duration: &[f32],
+    bsz: usize,
+    batch: bool,
+    sample_rate: i32,
+) -> Vec<Vec<f32>> {
+    (0..bsz)
+        .map(|i| {
+            let wav_slice = if batch {
+                let wav_len = wav.len() / bsz;
+                let actual_len = (sample_rate as f32 * duration[i]) as usize;
+                let wav_start = i * wav_len;
+                let wav_end = wav_start + actual_len.min(wav_len);
+                &wav[wav_start..wav_end]
+            } else {
+                // For non-batch mode, wav is a single concatenated audio
+                let actual_len = (sample_rate as f32 * duration[0]) as usize;
+                &wav[..actual_len.min(wav.len())]
+            };
+            wav_slice.to_vec()
+        })
+        .collect()
+}
+
+fn main() -> Result<()> {
+    println!("=== TTS Inference with ONNX Runtime (Rust) ===\n");
+
+    // --- 1. Parse arguments --- //
+    let args = Args::parse();
+    let total_step = resolve_total_step(&args);
+    // Same check the library makes, but before any model is loaded
+    check_total_step(total_step)?;
+    let speed = args.speed;
+    let n_test = args.n_test;
+    let mut voice_style_paths = split_values(&args.voice_style, &args.voice_delimiter);
+    if args.list_voices || !args.voice_style_id.is_empty() {
+        let library = VoiceLibrary::load(&args.voice_library)?;
+        if args.list_voices {
+            for (id, entry) in library.entries() {
+                println!(
+                    "{:<12} {:<20} {:<8} {:<4} {}",
+                    id,
+                    entry.name.as_deref().unwrap_or("-"),
+                    entry.gender.as_deref().unwrap_or("-"),
+                    entry.lang.as_deref().unwrap_or("-"),
+                    entry.path
+                );
+            }
+            return Ok(());
+        }
+        voice_style_paths = args
+            .voice_style_id
+            .iter()
+            .map(|id| library.resolve(id).map(|path| path.to_string_lossy().into_owned()))
+            .collect::<Result<_>>()?;
+    }
+    // A weighted mix acts as a single voice, labelled by its components for output names
+    let mix_components = if args.mix_weights.is_empty() {
+        Vec::new()
+    } else {
+        if args.mix_weights.len() != voice_style_paths.len() {
+            anyhow::bail!(
+                "--mix-weights has {} weights but {} voice styles were given",
+                args.mix_weights.len(),
+                voice_style_paths.len()
+            );
+        }
+        let label = voice_style_paths
+            .iter()
+            .zip(&args.mix_weights)
+            .map(|(path, weight)| {
+                let stem = Path::new(path).file_stem().unwrap_or_default().to_string_lossy();
+                format!("{}@{}", stem, weight)
+            })
+            .collect::<Vec<_>>()
+            .join("+");
+        mem::replace(&mut voice_style_paths, vec![format!("{}.mix", label)])
+    };
+    let mut text_list = split_values(&args.text, &args.text_delimiter);
+    if let Some(path) = &args.script {
+        if !mix_components.is_empty() {
+            anyhow::bail!("--script cannot be combined with --mix-weights");
+        }
+        let source = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let lines = parse_script(&source);
+        if lines.is_empty() {
+            anyhow::bail!("{} has no lines to synthesize", path.display());
+        }
+        // Tagged lines use the voice library; untagged ones the first --voice-style
+        let library = if lines.iter().any(|l| l.voice.is_some()) {
+            Some(VoiceLibrary::load(&args.voice_library)?)
+        } else {
+            None
+        };
+        let default_voice = voice_style_paths[0].clone();
+        voice_style_paths = lines
+            .iter()
+            .map(|line| match (&line.voice, &library) {
+                (Some(id), Some(library)) => library.resolve(id).map(|p| p.to_string_lossy().into_owned()),
+                _ => Ok(default_voice.clone()),
+            })
+            .collect::<Result<_>>()?;
+        text_list = lines.into_iter().map(|line| line.text).collect();
+    }
+    let text_list = &text_list;
+    let mut lang_list = args.lang.clone();
+    let save_dir = &args.save_dir;
+    // A script holds one utterance per line, synthesized together
+    let batch = args.batch || args.script.is_some();
+    let pad_to_max = args.pad_to_max;
+    let continue_on_error = args.continue_on_error;
+
+    if batch {
+        for (name, values) in [("voice styles", &mut voice_style_paths), ("languages", &mut lang_list)] {
+            if values.len() == text_list.len() {
+                continue;
+            }
+            // A single voice style or language applies to every text unless --strict-batch is set
+            if values.len() == 1 && !args.strict_batch {
+                *values = vec![values[0].clone(); text_list.len()];
+                continue;
+            }
+            let hint = if args.strict_batch {
+                "; --strict-batch requires one per text (drop it to broadcast a single value to all texts)"
+            } else {
+                "; pass one per text, or a single one to use for all texts"
+            };
+            anyhow::bail!(
+                "Number of {} ({}) must match number of texts ({}){}",
+                name,
+                values.len(),
+                text_list.len(),
+                hint
+            );
+        }
+    }
+    let voice_style_paths = &voice_style_paths;
+    let lang_list = &lang_list;
+
+    if args.spectrogram && !cfg!(feature = "spectrogram") {
+        anyhow::bail!("--spectrogram requires building with `--features spectrogram`");
+    }
+
+    if args.output_format == "opus" && !cfg!(feature = "opus") {
+        anyhow::bail!("--output-format opus requires building with `--features opus`");
+    }
+
+    if args.save_untruncated && !batch {
+        anyhow::bail!("--save-untruncated requires --batch");
+    }
+
+    if args.multichannel {
+        if !batch {
+            anyhow::bail!("--multichannel requires --batch");
+        }
+        if !args.mix.is_empty() {
+            anyhow::bail!("--multichannel cannot be combined with --mix");
+        }
+    }
+
+    if !args.mix.is_empty() {
+        if !batch {
+            anyhow::bail!("--mix requires --batch");
+        }
+        for (flag, len) in [("--mix", args.mix.len()), ("--mix-offsets", args.mix_offsets.len())] {
+            if len > 1 && len != text_list.len() {
+                anyhow::bail!("{} takes one value or one per text ({}), got {}", flag, text_list.len(), len);
+            }
+        }
+    }
+
+    if args.gains.len() > 1 && args.gains.len() != voice_style_paths.len() {
+        anyhow::bail!(
+            "--gains takes one value or one per item ({}), got {}",
+            voice_style_paths.len(),
+            args.gains.len()
+        );
+    }
+
+    if let Some(max_batch) = args.max_batch {
+        if max_batch == 0 {
+            anyhow::bail!("--max-batch must be at least 1");
+        }
+        if args.init_latent.is_some() && max_batch < text_list.len() {
+            anyhow::bail!("--init-latent cannot be combined with a --max-batch smaller than the batch");
+        }
+        if args.dump_latent.is_some() && max_batch < text_list.len() {
+            anyhow::bail!("--dump-latent cannot be combined with a --max-batch smaller than the batch");
+        }
+    }
+
+    if args.compress_ratio < 1.0 {
+        anyhow::bail!("--compress-ratio must be at least 1, got {}", args.compress_ratio);
+    }
+
+    if args.embed_audio && !args.metadata {
+        anyhow::bail!("--embed-audio requires --metadata");
+    }
+
+    if args.seed_per_item && args.seed.is_none() {
+        anyhow::bail!("--seed-per-item requires --seed");
+    }
+
+    if continue_on_error && batch && args.init_latent.is_some() {
+        anyhow::bail!("--init-latent cannot be combined with --continue-on-error in batch mode");
+    }
+    if continue_on_error && batch && args.dump_latent.is_some() {
+        anyhow::bail!("--dump-latent cannot be combined with --continue-on-error in batch mode");
+    }
+
+    let bsz = voice_style_paths.len();
+
+    if args.validate_only {
+        let code = match validate_model_dir(&args.onnx_dir, mix_components.first().unwrap_or(&voice_style_paths[0]), &args) {
+            Ok(()) => {
+                println!("\nPASS: {}", args.onnx_dir);
+                0
+            }
+            Err(e) => {
+                println!("\nFAIL: {}: {:#}", args.onnx_dir, e);
+                1
+            }
+        };
+        exit_process(code);
+    }
+
+    // --- 2. Load TTS components --- //
+    if let Some(dir) = &args.profile_dir {
+        fs::create_dir_all(dir)?;
+    }
+    let backend = resolve_backend(&args)?;
+    // Only CUDA exposes several devices; $SUPERTONIC_DEVICE is only read for CUDA
+    let device = match backend {
+        Backend::Cuda(device) => device as u32,
+        _ => match args.device {
+            Some(device) if device != 0 => {
+                anyhow::bail!("Device {} does not exist: only --backend cuda has devices other than 0", device)
+            }
+            _ => 0,
+        },
+    };
+    let (mut text_to_speech, load_timings) =
+        load_text_to_speech_timed(&args.onnx_dir, backend, args.profile_dir.as_deref())?;
+    for (session, provider) in text_to_speech.session_providers() {
+        let device = if provider == "CUDA" { device } else { 0 };
+        println!("Session {} bound to {} device {}", session, provider, device);
+    }
+    if args.load_timings {
+        let total = load_timings.total().as_secs_f64();
+        println!("Load timings ({:.3}s total):", total);
+        for (name, elapsed) in load_timings.entries() {
+            let secs = elapsed.as_secs_f64();
+            let share = if total > 0.0 { secs / total * 100.0 } else { 0.0 };
+            println!("  {:<24} {:>8.3}s  {:>5.1}%", name, secs, share);
+        }
+        println!();
+    }
+    if args.normalize_text {
+        text_to_speech.set_text_normalizer(Box::new(EnglishNormalizer));
+    }
+    text_to_speech.set_skip_normalization(args.skip_normalization);
+    text_to_speech.set_out_of_range_id(args.out_of_range_id);
+    if args.save_trajectory {
+        let dir = PathBuf::from(save_dir).join("trajectory");
+        fs::create_dir_all(&dir)?;
+        let sample_rate = text_to_speech.sample_rate;
+        let mut inference = 0;
+        text_to_speech.set_step_preview(Some(Box::new(move |step, wav| {
+            // Each inference (chunk or run) starts again at step 0
+            if step == 0 {
+                inference += 1;
+            }
+            let fname = if inference == 1 {
+                format!("step_{}.wav", step)
+            } else {
+                format!("step_{}_{}.wav", step, inference)
+            };
+            if let Err(e) = write_wav_file(dir.join(&fname), &AudioBuffer::mono(wav.to_vec(), sample_rate as u32)) {
+                eprintln!("Warning: failed to write trajectory {}: {:#}", fname, e);
+            }
+        })));
+    }
+    if let Some(path) = &args.indexer_patch {
+        let patch = load_indexer_patch(path)?;
+        text_to_speech.text_processor_mut().merge_indexer(&patch);
+        println!("Applied {} indexer patch entries from {}", patch.len(), path.display());
+    }
+    text_to_speech.check_sample_rate(args.strict)?;
+    text_to_speech.set_chunk_compress(args.chunk_compress)?;
+    text_to_speech.set_latent_dim(args.latent_dim)?;
+    text_to_speech.set_latent_rounding(parse_latent_rounding(&args.latent_rounding));
+    if let Some(max_latent_len) = args.max_latent_len {
+        text_to_speech.set_max_latent_len(max_latent_len);
+    }
+    if let Some(spec) = &args.chunk_pauses {
+        text_to_speech.set_chunk_pauses(Some(parse_chunk_pauses(spec)?));
+    }
+    if let Some(path) = &args.metrics_file {
+        text_to_speech.set_metrics_sink(Box::new(PrometheusFileSink::new(path)));
+    }
+    text_to_speech.set_vocoder_split(args.vocoder_split, args.vocoder_threads)?;
+    text_to_speech.set_concurrent_encode(!args.sequential_encode);
+    text_to_speech.set_max_wav_seconds(args.max_wav_seconds, parse_overlong_policy(&args.on_overlong));
+    if args.strict_text {
+        text_to_speech.set_unmappable_policy(UnmappableTextPolicy::Strict);
+    }
+    text_to_speech.set_latent_tile(args.latent_tile, args.latent_tile_overlap)?;
+    if args.monitor_latent {
+        text_to_speech.set_latent_monitor(Some(args.latent_norm_limit));
+    }
+
+    if args.print_config {
+        println!("{}", serde_json::to_string_pretty(&text_to_speech.effective_config())?);
+        shutdown_and_exit(text_to_speech, 0);
+    }
+
+    if let Some(path) = &args.vocode_only {
+        let latent: Array3<f32> = read_npy(path).with_context(|| format!("Failed to read latent from {}", path.display()))?;
+        let bsz = latent.dim().0;
+        let wav = timer("Vocoding latent", || text_to_speech.vocode(latent))?;
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        fs::create_dir_all(save_dir)?;
+        for (i, item) in wav.chunks(wav.len() / bsz).enumerate() {
+            let fname = if bsz > 1 { format!("{}_vocoded_{}.wav", stem, i + 1) } else { format!("{}_vocoded.wav", stem) };
+            save_output(&PathBuf::from(save_dir).join(fname), item, &args, text_to_speech.sample_rate, None)?;
+        }
+        shutdown_and_exit(text_to_speech, 0);
+    }
+
+    if args.dump_ids {
+        for (i, (text, lang)) in text_list.iter().zip(lang_list.iter()).enumerate() {
+            let trace = text_to_speech.text_processor().trace(text, lang)?;
+            println!("[{}] {:?}", i, trace.processed);
+            for (j, (c, (&cp, &id))) in trace
+                .processed
+                .chars()
+                .zip(trace.codepoints.iter().zip(trace.ids.iter()))
+                .enumerate()
+            {
+                let marker = if id < 0 { "  <-- OOV" } else { "" };
+                println!("  {:>4}  {:?}  U+{:04X}  {:>6}{}", j, c, cp, id, marker);
+            }
+        }
+        println!();
+    }
+
+    // --- 3. Load voice styles --- //
+    // Plain voice styles split by --max-batch are loaded per sub-batch; up front only the
+    // first one is needed (for sweeps and the single-item paths)
+    let lazy_styles = batch
+        && !continue_on_error
+        && args.max_batch.is_some_and(|max_batch| max_batch < bsz)
+        && mix_components.is_empty()
+        && args.speaker_embedding.is_empty()
+        && args.ids_file.is_none();
+    let style = build_style(
+        if lazy_styles { &voice_style_paths[..1] } else { voice_style_paths },
+        &mix_components,
+        &args.mix_weights,
+        &args.speaker_embedding,
+        &args.style_scale,
+        true,
+    )?;
+
+    let initial_latent: Option<Array3<f32>> = match &args.init_latent {
+        Some(path) => Some(read_npy(path).with_context(|| {
+            format!("Failed to read initial latent from {}", path.display())
+        })?),
+        None => None,
+    };
+
+    // --- 4. Synthesize speech --- //
+    fs::create_dir_all(save_dir)?;
+
+    if !args.sweep_steps.is_empty() {
+        run_step_sweep(
+            &mut text_to_speech,
+            &args,
+            &text_list[0],
+            &lang_list[0],
+            &style.select(0),
+            &args.sweep_steps,
+        )?;
+        shutdown_and_exit(text_to_speech, 0);
+    }
+
+    match args.seed {
+        Some(seed) if args.seed_per_item => text_to_speech.set_item_seed(Some(seed)),
+        seed => text_to_speech.set_seed(seed),
+    }
+    let seed_label = args.seed.map_or_else(|| "random".to_string(), |seed| seed.to_string());
+
+    if args.check_determinism {
+        let code = match check_determinism(&mut text_to_speech, &args, &text_list[0], &lang_list[0], &style.select(0), total_step) {
+            Ok(()) => 0,
+            Err(e) => {
+                println!("FAIL: {:#}", e);
+                1
+            }
+        };
+        shutdown_and_exit(text_to_speech, code);
+    }
+
+    if let Some(sentence) = &args.audition {
+        run_audition(&mut text_to_speech, &args, sentence, &lang_list[0], total_step)?;
+        shutdown_and_exit(text_to_speech, 0);
+    }
+
+    if let Some(path) = &args.replay {
+        run_replay(&mut text_to_speech, &args, path)?;
+        shutdown_and_exit(text_to_speech, 0);
+    }
+
+    if let Some(path) = &args.ids_file {
+        run_ids_file(&mut text_to_speech, &args, path, &style, total_step)?;
+        shutdown_and_exit(text_to_speech, 0);
+    }
+
+    if args.stream_stdin {
+        run_stdin_stream(&mut text_to_speech, &args, &lang_list[0], &style.select(0), total_step)?;
+        shutdown_and_exit(text_to_speech, 0);
+    }
+
+    let loudness_target = match &args.match_loudness {
+        Some(path) => {
+            let stats = audio_stats(&read_wav_file(path)?.samples);
+            let (target, measure) = match args.match_measure.as_str() {
+                "peak" => (stats.peak, LevelMeasure::Peak),
+                _ => (stats.rms, LevelMeasure::Rms),
+            };
+            if target <= 0.0 {
+                anyhow::bail!("Reference {} is silent", path.display());
+            }
+            println!("Matching {} of {}: {:.1} dBFS", args.match_measure, path.display(), 20.0 * target.log10());
+            Some((target, measure))
+        }
+        None => None,
+    };
+
+    let sample_rate = text_to_speech.sample_rate;
+    let mut failures: Vec<(usize, usize, String)> = Vec::new();
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+    let output_path_for = |i: usize, n: usize| -> PathBuf {
+        let voice_path = &voice_style_paths[i.min(voice_style_paths.len() - 1)];
+        let voice_name = sanitize_filename(
+            &Path::new(voice_path)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            50,
+        );
+        let stem = if args.content_hash_names {
+            content_hash(&[
+                &text_list[i],
+                &lang_list[i.min(lang_list.len() - 1)],
+                voice_path,
+                &total_step.to_string(),
+                &speed.to_string(),
+                &seed_label,
+            ])
+        } else {
+            render_name_template(
+                &args.name_template,
+                &[
+                    ("text", sanitize_filename(&text_list[i], 20)),
+                    ("index", i.to_string()),
+                    ("run", (n + 1).to_string()),
+                    ("voice", voice_name.clone()),
+                    ("seed", seed_label.clone()),
+                    ("timestamp", timestamp.to_string()),
+                ],
+            )
+        };
+        let dir = if args.group_by_voice {
+            PathBuf::from(save_dir).join(&voice_name)
+        } else {
+            PathBuf::from(save_dir)
+        };
+        dir.join(format!("{}.{}", stem, args.output_format))
+    };
+    let is_cached = |path: &Path| args.content_hash_names && !args.overwrite && path.exists();
+
+    if args.dry_run {
+        // Only the duration predictor runs; sizes follow from the predicted lengths
+        let durations = if batch {
+            let chunk = args.max_batch.unwrap_or(bsz);
+            let mut durations = Vec::with_capacity(bsz);
+            for start in (0..bsz).step_by(chunk) {
+                let end = (start + chunk).min(bsz);
+                let sub_style = if lazy_styles {
+                    build_style(&voice_style_paths[start..end], &[], &[], &[], &args.style_scale, false)?
+                } else {
+                    style.slice(start..end)
+                };
+                durations.extend(text_to_speech.predict_durations(
+                    &text_list[start..end],
+                    &lang_list[start..end],
+                    &sub_style,
+                    speed,
+                )?);
+            }
+            durations
+        } else {
+            vec![text_to_speech.predict_call_duration(&text_list[0], &lang_list[0], &style, speed, 0.3)?]
+        };
+        let mut lens: Vec<usize> = durations.iter().map(|d| (sample_rate as f32 * d) as usize).collect();
+        if pad_to_max && batch {
+            let max_len = lens.iter().copied().max().unwrap_or(0);
+            lens.iter_mut().for_each(|len| *len = max_len);
+        }
+        let padding = ((args.pad_start_ms + args.pad_end_ms) * sample_rate as f32 / 1000.0) as usize;
+
+        let mut files = Vec::new();
+        for n in 0..n_test {
+            if args.multichannel {
+                let samples = lens.iter().copied().max().unwrap_or(0) + padding;
+                let path = PathBuf::from(save_dir).join(format!("multichannel_{}.wav", n + 1));
+                files.push(DryRunFile::new(&path, samples, bsz as u16, sample_rate));
+            } else if !args.mix.is_empty() {
+                let samples = lens
+                    .iter()
+                    .enumerate()
+                    .map(|(i, len)| (broadcast(&args.mix_offsets, i, 0.0) * sample_rate as f32) as usize + len)
+                    .max()
+                    .unwrap_or(0);
+                let path = PathBuf::from(save_dir).join(format!("mix_{}.{}", n + 1, args.output_format));
+                files.push(DryRunFile::new(&path, samples + padding, 1, sample_rate));
+            } else {
+                for (i, len) in lens.iter().enumerate() {
+                    files.push(DryRunFile::new(&output_path_for(i, n), len + padding, 1, sample_rate));
+                }
+            }
+        }
+        if args.output_format != "wav" {
+            eprintln!("Warning: sizes are for 16-bit WAV; {} output is smaller", args.output_format);
+        }
+        let report = DryRunReport { total_bytes: files.iter().map(|f| f.bytes).sum(), files };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        shutdown_and_exit(text_to_speech, 0);
+    }
+
+    for n in 0..n_test {
+        println!("\n[{}/{}] Starting synthesis...", n + 1, n_test);
+
+        if args.mix.is_empty() && !args.multichannel && (0..bsz).all(|i| is_cached(&output_path_for(i, n))) {
+            println!("Skipped: all outputs already exist");
+            continue;
+        }
+
+        if let Some(path) = &args.request_log {
+            let record = RequestRecord {
+                text: text_list.clone(),
+                lang: lang_list.clone(),
+                voice_style: voice_style_paths.clone(),
+                mix_components: mix_components.clone(),
+                mix_weights: args.mix_weights.clone(),
+                speaker_embedding: args.speaker_embedding.clone(),
+                style_scale: args.style_scale.clone(),
+                total_step,
+                speed,
+                seed: args.seed,
+                seed_per_item: args.seed_per_item,
+                batch,
+                each: continue_on_error && batch,
+                max_batch: args.max_batch,
+                latent_rounding: args.latent_rounding.clone(),
+                chunk_compress: args.chunk_compress,
+                latent_dim: args.latent_dim,
+                chunk_pauses: args.chunk_pauses.clone(),
+                normalize_text: args.normalize_text,
+                skip_normalization: args.skip_normalization,
+                out_of_range_id: args.out_of_range_id,
+                indexer_patch: args.indexer_patch.clone(),
+                strict_text: args.strict_text,
+                max_wav_seconds: args.max_wav_seconds,
+                on_overlong: args.on_overlong.clone(),
+                latent_tile: args.latent_tile,
+                latent_tile_overlap: args.latent_tile_overlap,
+                max_latent_len: args.max_latent_len,
+                init_latent: args.init_latent.clone(),
+                onnx_dir: args.onnx_dir.clone(),
+                run: n,
+            };
+            append_request(path, &record)?;
+        }
+
+        if let Some(path) = &args.dump_latent {
+            // Keep one dump per run when generating several times
+            let path = if n_test > 1 {
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                path.with_file_name(format!("{}_{}.npy", stem, n + 1))
+            } else {
+                path.clone()
+            };
+            text_to_speech.set_dump_latent(Some(path));
+        }
+
+        // Whole per-item vocoder outputs, kept only for --save-untruncated
+        let mut untruncated: Vec<(usize, Vec<f32>)> = Vec::new();
+
+        let synthesized = if continue_on_error && batch {
+            // Synthesize each item on its own so one failure doesn't discard the rest
+            timer("Generating speech from text", || {
+                Ok(text_to_speech.batch_each(text_list, lang_list, &style, total_step, speed))
+            })?
+            .into_iter()
+            .enumerate()
+            .map(|(i, r)| r.map(|(w, d)| {
+                let actual_len = (sample_rate as f32 * d) as usize;
+                let trimmed = w[..actual_len.min(w.len())].to_vec();
+                if args.save_untruncated {
+                    untruncated.push((i, w));
+                }
+                trimmed
+            }))
+            .collect()
+        } else {
+            let result = if batch {
+                timer("Generating speech from text", || {
+                    // Run at most --max-batch items at once; each item is trimmed to its own duration
+                    let chunk = args.max_batch.unwrap_or(bsz);
+                    let mut outputs = Vec::with_capacity(bsz);
+                    for start in (0..bsz).step_by(chunk) {
+                        let end = (start + chunk).min(bsz);
+                        let sub_style = if lazy_styles {
+                            build_style(&voice_style_paths[start..end], &[], &[], &[], &args.style_scale, false)?
+                        } else {
+                            style.slice(start..end)
+                        };
+                        let (wav, duration) = text_to_speech.batch(
+                            &text_list[start..end],
+                            &lang_list[start..end],
+                            &sub_style,
+                            total_step,
+                            speed,
+                            initial_latent.clone(),
+                        )?;
+                        outputs.extend(split_outputs(&wav, &duration, end - start, true, sample_rate));
+                        if args.save_untruncated {
+                            let wav_len = wav.len() / (end - start);
+                            untruncated.extend((start..end).zip(wav.chunks(wav_len).map(<[f32]>::to_vec)));
+                        }
+                    }
+                    Ok(outputs)
+                })
+            } else {
+                timer("Generating speech from text", || {
+                    text_to_speech
+                        .call(&text_list[0], &lang_list[0], &style, total_step, speed, 0.3, initial_latent.clone(), None)
+                        .map(|(wav, duration)| split_outputs(&wav, &[duration], bsz, false, sample_rate))
+                })
+            };
+
+            match result {
+                Ok(outputs) => outputs.into_iter().map(Ok).collect(),
+                Err(e) if continue_on_error => vec![Err(e)],
+                Err(e) => return Err(e),
+            }
+        };
+
+        for (i, wav) in &untruncated {
+            let path = output_path_for(*i, n);
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let full_path = path.with_file_name(format!("{}_full.wav", stem));
+            if let Some(parent) = full_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            write_wav_file_with_options(&full_path, wav, sample_rate, pcm_options(&args))?;
+            println!("Saved: {} (untruncated, {:.2} s)", full_path.display(), wav.len() as f32 / sample_rate as f32);
+        }
+
+        // Save outputs
+        let max_len = synthesized
+            .iter()
+            .filter_map(|r| r.as_ref().ok())
+            .map(|w| w.len())
+            .max()
+            .unwrap_or(0);
+
+        let mut outputs = Vec::new();
+        for (i, output) in synthesized.into_iter().enumerate() {
+            match output {
+                Ok(wav) => outputs.push((i, wav)),
+                Err(e) => {
+                    println!("Failed: item {} ({:#})", i, e);
+                    failures.push((n + 1, i, format!("{:#}", e)));
+                }
+            }
+        }
+        if !args.gains.is_empty() {
+            for (i, wav) in outputs.iter_mut() {
+                apply_gain(wav, broadcast(&args.gains, *i, 1.0));
+            }
+        }
+        if args.compress {
+            for (_, wav) in outputs.iter_mut() {
+                compress(
+                    wav,
+                    args.compress_threshold,
+                    args.compress_ratio,
+                    args.compress_attack_ms,
+                    args.compress_release_ms,
+                    sample_rate,
+                );
+            }
+        }
+        // Matched before postprocessing so added padding doesn't dilute the RMS
+        if let Some((target, measure)) = loudness_target {
+            for (_, wav) in outputs.iter_mut() {
+                match_level(wav, target, measure);
+            }
+        }
+
+        if args.multichannel {
+            // One channel per batch item, in input order; failed items stay silent
+            let mut tracks = vec![Vec::new(); bsz];
+            for (i, wav) in outputs {
+                tracks[i] = postprocess(wav, &args, sample_rate);
+            }
+            let output_path = PathBuf::from(save_dir).join(format!("multichannel_{}.wav", n + 1));
+            write_multichannel_wav(&output_path, &tracks, sample_rate, pcm_options(&args))?;
+            println!("Saved: {} ({} channels)", output_path.display(), tracks.len());
+            continue;
+        }
+
+        if !args.mix.is_empty() {
+            // Mix all items into a single track instead of writing one file each. Each item
+            // gets its onset fade before mixing, so the mix itself is not faded again
+            if !args.no_onset_fade {
+                for (_, wav) in outputs.iter_mut() {
+                    onset_fade(wav, onset_fade_len(&args, sample_rate));
+                }
+            }
+            let tracks: Vec<&[f32]> = outputs.iter().map(|(_, w)| w.as_slice()).collect();
+            let gains: Vec<f32> = outputs.iter().map(|&(i, _)| broadcast(&args.mix, i, 1.0)).collect();
+            let offsets: Vec<usize> = outputs
+                .iter()
+                .map(|&(i, _)| (broadcast(&args.mix_offsets, i, 0.0) * sample_rate as f32) as usize)
+                .collect();
+            let mixed = postprocess_after_fade(mix_tracks(&tracks, &gains, &offsets), &args, sample_rate);
+            let output_path = PathBuf::from(save_dir).join(format!("mix_{}.{}", n + 1, args.output_format));
+            save_output(&output_path, &mixed, &args, sample_rate, None)?;
+            continue;
+        }
+
+        for (i, wav) in outputs {
+            let output_path = output_path_for(i, n);
+            if is_cached(&output_path) {
+                println!("Skipped (exists): {}", output_path.display());
+                continue;
+            }
+            if let Some(parent) = output_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut wav = wav;
+            if pad_to_max && batch {
+                wav.resize(max_len, 0.0);
+            }
+            let wav = postprocess(wav, &args, sample_rate);
+            let item = ItemInfo {
+                text: &text_list[i],
+                lang: &lang_list[i.min(lang_list.len() - 1)],
+                voice_style: &voice_style_paths[i.min(voice_style_paths.len() - 1)],
+            };
+            save_output(&output_path, &wav, &args, sample_rate, Some(&item))?;
+        }
+    }
+
+    if !failures.is_empty() {
+        println!("\n=== Synthesis finished with {} failure(s) ===", failures.len());
+        for (run, index, message) in &failures {
+            println!("  run {}, item {}: {}", run, index, message);
+        }
+
+        shutdown_and_exit(text_to_speech, 1);
+    }
+
+    println!("\n=== Synthesis completed successfully! ===");
+
+    shutdown_and_exit(text_to_speech, 0);
+}
+
+/// Execution provider from --backend, or CUDA for --use-gpu
+///
+/// The CUDA device is --device, else $SUPERTONIC_DEVICE, else 0.
+fn resolve_backend(args: &Args) -> Result<Backend> {
+    Ok(match args.backend.as_str() {
+        "coreml" => Backend::CoreMl,
+        _ if args.use_gpu || args.backend == "cuda" => {
+            let device = match (args.device, std::env::var("SUPERTONIC_DEVICE")) {
+                (Some(device), _) => device,
+                (None, Ok(value)) => value
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("Invalid SUPERTONIC_DEVICE {:?}: expected a device ordinal", value))?,
+                (None, Err(_)) => 0,
+            };
+            Backend::Cuda(device as i32)
+        }
+        _ => Backend::Cpu,
+    })
+}
+
+/// Denoising steps from an explicit --total-step, else from the --quality preset
+fn resolve_total_step(args: &Args) -> usize {
+    args.total_step.unwrap_or(match args.quality.as_str() {
+        "draft" => 2,
+        "high" => 15,
+        _ => 5,
+    })
+}
+
+/// Load every model artifact and run a short synthesis, checking the output is sane
+fn validate_model_dir(onnx_dir: &str, voice_style_path: &str, args: &Args) -> Result<()> {
+    const PHRASE: &str = "Hello, this is a test.";
+
+    let mut text_to_speech = timer("Loading config, indexer and sessions", || {
+        load_text_to_speech_timed(onnx_dir, resolve_backend(args)?, None).map(|(tts, _)| tts)
+    })?;
+    text_to_speech.check_sample_rate(args.strict)?;
+    let cfg = text_to_speech.config();
+    println!(
+        "  sample_rate={}, base_chunk_size={}, chunk_compress_factor={}, latent_dim={}",
+        cfg.ae.sample_rate, cfg.ae.base_chunk_size, cfg.ttl.chunk_compress_factor, cfg.ttl.latent_dim
+    );
+
+    let style = load_voice_style(&[voice_style_path.to_string()], false)?;
+    let (wav, duration) = timer("Synthesizing test phrase", || {
+        text_to_speech.call(PHRASE, "en", &style, resolve_total_step(args), args.speed, 0.3, None, None)
+    })?;
+
+    let peak = wav.iter().fold(0.0f32, |a, &b| a.max(b.abs()));
+    println!("  samples={}, duration={:.2}s, peak={:.4}", wav.len(), duration, peak);
+
+    if wav.is_empty() || duration <= 0.0 {
+        anyhow::bail!("Synthesis produced no audio");
+    }
+    if let Some(pos) = wav.iter().position(|s| !s.is_finite()) {
+        anyhow::bail!("Output contains a non-finite sample at index {}", pos);
+    }
+    if peak == 0.0 {
+        anyhow::bail!("Output is entirely silent");
+    }
+
+    release(text_to_speech);
+    Ok(())
+}
+
+/// Parse `period=300,comma=150,newline=500` (milliseconds); omitted keys keep the 300 ms default
+fn parse_chunk_pauses(spec: &str) -> Result<ChunkPauses> {
+    let mut pauses = ChunkPauses::uniform(0.3);
+    for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (key, value) = entry
+            .split_once('=')
+            .with_context(|| format!("Expected key=ms in --chunk-pauses, got {:?}", entry))?;
+        let seconds = value
+            .trim()
+            .parse::<f32>()
+            .with_context(|| format!("Invalid pause {:?} in --chunk-pauses", value))?
+            / 1000.0;
+        match key.trim() {
+            "period" => pauses.period = seconds,
+            "comma" => pauses.comma = seconds,
+            "newline" => pauses.newline = seconds,
+            other => anyhow::bail!("Unknown --chunk-pauses key {:?} (expected period, comma or newline)", other),
+        }
+    }
+    Ok(pauses)
+}
+
+/// Parse a --gains value into a linear factor: plain numbers are linear, a `dB` suffix means decibels
+fn parse_gain(value: &str) -> Result<f32> {
+    let value = value.trim();
+    let lower = value.to_ascii_lowercase();
+    match lower.strip_suffix("db") {
+        Some(db) => {
+            let db: f32 = db.trim().parse().with_context(|| format!("Invalid dB gain {:?}", value))?;
+            Ok(10f32.powf(db / 20.0))
+        }
+        None => {
+            let gain: f32 = value.parse().with_context(|| format!("Invalid gain {:?}", value))?;
+            if gain < 0.0 {
+                anyhow::bail!("Linear gain {} must not be negative (use a dB suffix for attenuation in dB)", gain);
+            }
+            Ok(gain)
+        }
+    }
+}
+
+/// Parse one --eq band given as freq:gain_db:q
+fn parse_eq_band(spec: &str) -> Result<BandSpec> {
+    let fields: Vec<&str> = spec.split(':').map(str::trim).collect();
+    let [freq, gain, q] = fields[..] else {
+        anyhow::bail!("Expected freq:gain_db:q in --eq, got {:?}", spec);
+    };
+    let parse = |name: &str, value: &str| {
+        value
+            .parse::<f32>()
+            .with_context(|| format!("Invalid {} {:?} in --eq band {:?}", name, value, spec))
+    };
+    let band = BandSpec { freq_hz: parse("frequency", freq)?, gain_db: parse("gain", gain)?, q: parse("Q", q)? };
+    if band.freq_hz <= 0.0 || band.q <= 0.0 {
+        anyhow::bail!("--eq band {:?} needs a positive frequency and Q", spec);
+    }
+    Ok(band)
+}
+
+/// BWF `bext` fields from the --bwf-* flags, or None when none of them is given
+fn bext_info(args: &Args) -> Result<Option<BextInfo>> {
+    if args.bwf_description.is_none() && args.bwf_originator.is_none() && args.bwf_originator_ref.is_none() {
+        return Ok(None);
+    }
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let info = BextInfo {
+        description: args.bwf_description.clone().unwrap_or_default(),
+        originator: args.bwf_originator.clone().unwrap_or_default(),
+        originator_reference: args.bwf_originator_ref.clone().unwrap_or_default(),
+        ..BextInfo::default()
+    };
+    Ok(Some(info.with_timestamp(timestamp)))
+}
+
+/// Load the batch style: voice style files (or one weighted mix of `mix_components` for
+/// every item), optionally replaced by speaker embeddings, then scaled
+fn build_style(
+    voice_style_paths: &[String],
+    mix_components: &[String],
+    mix_weights: &[f32],
+    speaker_embedding: &[PathBuf],
+    style_scale: &[f32],
+    verbose: bool,
+) -> Result<Style> {
+    let mut style = if mix_components.is_empty() {
+        load_voice_style(voice_style_paths, verbose)?
+    } else {
+        let styles = mix_components
+            .iter()
+            .map(|path| load_voice_style(std::slice::from_ref(path), verbose))
+            .collect::<Result<Vec<_>>>()?;
+        let mixed = Style::mix(&styles, mix_weights)?;
+        concat_styles(&vec![mixed; voice_style_paths.len()])?
+    };
+    if !speaker_embedding.is_empty() {
+        // The voice styles only supply the per-item shapes the embeddings are mapped onto
+        let (n, ttl_rows, ttl_cols) = style.ttl.dim();
+        let (_, dp_rows, dp_cols) = style.dp.dim();
+        if speaker_embedding.len() != 1 && speaker_embedding.len() != n {
+            anyhow::bail!(
+                "Got {} speaker embeddings for {} voice styles; pass one per item or a single one",
+                speaker_embedding.len(),
+                n
+            );
+        }
+        let styles = (0..n)
+            .map(|i| {
+                let path = &speaker_embedding[i.min(speaker_embedding.len() - 1)];
+                SpeakerEmbedding::load(path)?
+                    .to_style([ttl_rows, ttl_cols], [dp_rows, dp_cols])
+                    .with_context(|| format!("Invalid speaker embedding {}", path.display()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        style = concat_styles(&styles)?;
+    }
+    match style_scale {
+        [] => {}
+        [factor] => style = style.scale(*factor, *factor),
+        [ttl_factor, dp_factor] => style = style.scale(*ttl_factor, *dp_factor),
+        _ => anyhow::bail!("--style-scale takes one or two values"),
+    }
+    Ok(style)
+}
+
+/// Latent rounding named by --latent-rounding
+fn parse_latent_rounding(name: &str) -> LatentRounding {
+    match name {
+        "floor" => LatentRounding::Floor,
+        "round" => LatentRounding::Round,
+        _ => LatentRounding::Ceil,
+    }
+}
+
+fn parse_overlong_policy(name: &str) -> OverlongPolicy {
+    match name {
+        "error" => OverlongPolicy::Error,
+        _ => OverlongPolicy::Truncate,
+    }
+}
+
+/// Pick the per-item value from a list that holds either one value per item or a single shared one
+fn broadcast(values: &[f32], index: usize, default: f32) -> f32 {
+    match values.len() {
+        0 => default,
+        1 => values[0],
+        _ => values[index],
+    }
+}
+
+/// Quantization settings selected by --pcm-scale and --pcm-rounding
+fn pcm_options(args: &Args) -> PcmOptions {
+    PcmOptions {
+        scaling: if args.pcm_scale == "32768" { PcmScaling::Full } else { PcmScaling::Symmetric },
+        rounding: if args.pcm_rounding == "nearest" { PcmRounding::Nearest } else { PcmRounding::Truncate },
+    }
+}
+
+/// Inputs that produced one output file
+#[derive(Serialize)]
+struct ItemInfo<'a> {
+    text: &'a str,
+    lang: &'a str,
+    voice_style: &'a str,
+}
+
+/// Planned outputs and their estimated sizes, printed by --dry-run
+#[derive(Serialize)]
+struct DryRunReport {
+    files: Vec<DryRunFile>,
+    total_bytes: usize,
+}
+
+#[derive(Serialize)]
+struct DryRunFile {
+    file: String,
+    duration_sec: f32,
+    channels: u16,
+    bytes: usize,
+}
+
+impl DryRunFile {
+    fn new(path: &Path, samples: usize, channels: u16, sample_rate: i32) -> Self {
+        DryRunFile {
+            file: path.display().to_string(),
+            duration_sec: samples as f32 / sample_rate as f32,
+            channels,
+            bytes: estimate_wav_bytes(samples, 16, channels),
+        }
+    }
+}
+
+/// JSON sidecar written next to an output with --metadata
+#[derive(Serialize)]
+struct OutputMetadata<'a> {
+    file: String,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    item: Option<&'a ItemInfo<'a>>,
+    total_step: usize,
+    speed: f32,
+    seed: Option<u64>,
+    sample_rate: i32,
+    duration_sec: f32,
+    /// Near-silent audio (below --silence-threshold) at the head and tail
+    leading_silence_sec: f32,
+    trailing_silence_sec: f32,
+    stats: AudioStats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    audio_base64: Option<String>,
+}
+
+/// Audio to write for `wav` under the `--empty-audio` policy, or None to skip the file
+///
+/// A header-only file is rejected by some players, so zero-length audio is either
+/// skipped or replaced with 10 ms of silence; anything else is passed through.
+fn resolve_empty_audio<'a>(wav: &'a [f32], policy: &str, sample_rate: i32) -> Option<Cow<'a, [f32]>> {
+    if !wav.is_empty() {
+        return Some(Cow::Borrowed(wav));
+    }
+    match policy {
+        "skip" => None,
+        _ => Some(Cow::Owned(vec![0.0; (sample_rate / 100) as usize])),
+    }
+}
+
+/// Write processed audio in the requested format, plus any side outputs
+fn save_output(
+    output_path: &Path,
+    wav: &[f32],
+    args: &Args,
+    sample_rate: i32,
+    item: Option<&ItemInfo>,
+) -> Result<()> {
+    if wav.is_empty() {
+        let action = if args.empty_audio == "skip" { "not writing" } else { "writing 10 ms of silence to" };
+        eprintln!("Warning: synthesized audio is empty, {} {}", action, output_path.display());
+    }
+    let Some(wav) = resolve_empty_audio(wav, &args.empty_audio, sample_rate) else {
+        return Ok(());
+    };
+    let wav = &wav[..];
+
+    #[cfg(feature = "opus")]
+    if args.output_format == "opus" {
+        supertonic::helper::write_opus_file(output_path, wav, sample_rate, args.opus_bitrate)?;
+    }
+    if args.output_format == "wav" {
+        let dithered;
+        let wav = if args.dither {
+            let mut samples = wav.to_vec();
+            let mut rng = args.dither_seed.map(StdRng::seed_from_u64);
+            tpdf_dither(&mut samples, 16, rng.as_mut().map(|r| r as &mut dyn rand::RngCore));
+            dithered = samples;
+            &dithered[..]
+        } else {
+            wav
+        };
+        match bext_info(args)? {
+            Some(info) => write_bwf_file(output_path, wav, sample_rate, pcm_options(args), &info)?,
+            None => write_wav_file_with_options(output_path, wav, sample_rate, pcm_options(args))?,
+        }
+    }
+    println!("Saved: {}", output_path.display());
+
+    if args.verbose || args.metadata {
+        let stats = audio_stats(wav);
+        let (leading, trailing) = silence_bounds(wav, 10f32.powf(args.silence_threshold / 20.0));
+        let leading_silence_sec = leading as f32 / sample_rate as f32;
+        let trailing_silence_sec = trailing as f32 / sample_rate as f32;
+        if args.verbose {
+            println!(
+                "  peak={:.4} ({:.1} dBFS), rms={:.4} ({:.1} dBFS), clipped={}",
+                stats.peak, stats.peak_dbfs, stats.rms, stats.rms_dbfs, stats.clipped_samples
+            );
+            println!(
+                "  leading silence={:.3}s, trailing silence={:.3}s",
+                leading_silence_sec, trailing_silence_sec
+            );
+        }
+        if args.metadata {
+            let duration_sec = wav.len() as f32 / sample_rate as f32;
+            let audio_base64 = if !args.embed_audio {
+                None
+            } else if duration_sec > args.embed_audio_max_sec {
+                eprintln!(
+                    "Warning: {} is {:.1} s, longer than --embed-audio-max-sec {:.1}; not embedding it",
+                    output_path.display(),
+                    duration_sec,
+                    args.embed_audio_max_sec
+                );
+                None
+            } else if args.output_format == "wav" {
+                // Embed exactly the file just written, BWF chunk and all
+                Some(base64_encode(&fs::read(output_path)?))
+            } else {
+                let buffer = AudioBuffer::mono(wav.to_vec(), sample_rate as u32);
+                Some(base64_encode(&buffer.to_wav_bytes(16)?))
+            };
+            let metadata = OutputMetadata {
+                file: output_path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+                item,
+                total_step: resolve_total_step(args),
+                speed: args.speed,
+                seed: args.seed,
+                sample_rate,
+                duration_sec,
+                leading_silence_sec,
+                trailing_silence_sec,
+                stats,
+                audio_base64,
+            };
+            let json_path = output_path.with_extension("json");
+            fs::write(&json_path, serde_json::to_string_pretty(&metadata)?)?;
+            println!("Saved: {}", json_path.display());
+        }
+    }
+
+    if args.raw_output {
+        let raw_path = output_path.with_extension("raw");
+        let endian = if args.raw_endian == "big" { Endian::Big } else { Endian::Little };
+        write_raw_pcm(&raw_path, wav, endian)?;
+        println!("Saved: {}", raw_path.display());
+    }
+
+    #[cfg(feature = "spectrogram")]
+    if args.spectrogram {
+        let png_path = output_path.with_extension("png");
+        supertonic::helper::write_spectrogram_png(&png_path, wav)?;
+        println!("Saved: {}", png_path.display());
+    }
+
+    Ok(())
+}
+
+/// Apply the optional output processing requested on the command line
+fn postprocess(wav: Vec<f32>, args: &Args, sample_rate: i32) -> Vec<f32> {
+    let mut wav = wav;
+
+    if !args.no_onset_fade {
+        onset_fade(&mut wav, onset_fade_len(args, sample_rate));
+    }
+
+    postprocess_after_fade(wav, args, sample_rate)
+}
+
+/// `postprocess` without the onset fade, for a mix whose items were faded individually
+fn postprocess_after_fade(wav: Vec<f32>, args: &Args, sample_rate: i32) -> Vec<f32> {
+    let mut wav = wav;
+
+    if let Some(cutoff_hz) = args.highpass {
+        highpass(&mut wav, sample_rate, cutoff_hz);
+    }
+
+    if !args.eq.is_empty() {
+        Equalizer::new(args.eq.clone()).apply(&mut wav, sample_rate);
+    }
+
+    if let Some(repeats) = args.r#loop {
+        let fade_len = (args.loop_crossfade_ms * sample_rate as f32 / 1000.0) as usize;
+        wav = make_loop(&wav, repeats, fade_len);
+    }
+
+    if args.pad_start_ms > 0.0 || args.pad_end_ms > 0.0 {
+        let start = (args.pad_start_ms * sample_rate as f32 / 1000.0) as usize;
+        let end = (args.pad_end_ms * sample_rate as f32 / 1000.0) as usize;
+        wav = pad_silence(&wav, start, end);
+    }
+
+    if let Some(dbfs) = args.limit {
+        limit_peaks(&mut wav, 10f32.powf(dbfs / 20.0));
+    }
+
+    wav
+}
+
+/// Length in samples of the --onset-fade-ms fade-in
+fn onset_fade_len(args: &Args, sample_rate: i32) -> usize {
+    (args.onset_fade_ms * sample_rate as f32 / 1000.0) as usize
+}
+
+fn shutdown_and_exit(mut text_to_speech: TextToSpeech, code: i32) -> ! {
+    // Profiles are only complete once each session ends profiling
+    match text_to_speech.end_profiling() {
+        Ok(files) => files.iter().for_each(|f| println!("Profile: {}", f.display())),
+        Err(e) => eprintln!("Warning: failed to finish profiling: {:#}", e),
+    }
+    release(text_to_speech);
+    exit_process(code);
+}
+
+/// Drop the ONNX sessions in order, or leak them with the skip-teardown feature
+fn release(text_to_speech: TextToSpeech) {
+    #[cfg(feature = "skip-teardown")]
+    mem::forget(text_to_speech);
+    #[cfg(not(feature = "skip-teardown"))]
+    text_to_speech.shutdown();
+}
+
+fn exit_process(code: i32) -> ! {
+    // _exit bypasses every cleanup handler, including ONNX Runtime's (mutex issues on macOS)
+    #[cfg(feature = "skip-teardown")]
+    unsafe {
+        libc::_exit(code);
+    }
+    #[cfg(not(feature = "skip-teardown"))]
+    std::process::exit(code);
+}
+
+/// Synthesize `text` twice from --seed and require byte-identical 16-bit WAV output
+fn check_determinism(
+    text_to_speech: &mut TextToSpeech,
+    args: &Args,
+    text: &str,
+    lang: &str,
+    style: &Style,
+    total_step: usize,
+) -> Result<()> {
+    let Some(seed) = args.seed else {
+        anyhow::bail!("--check-determinism requires --seed");
+    };
+    let mut runs = Vec::with_capacity(2);
+    for run in 1..=2 {
+        let (wav, _) = timer(&format!("Synthesizing run {} with seed {}", run, seed), || {
+            text_to_speech.call(text, lang, style, total_step, args.speed, 0.3, None, Some(seed))
+        })?;
+        runs.push(text_to_speech.audio_buffer(wav).to_wav_bytes(16)?);
+    }
+    if runs[0] != runs[1] {
+        let first = runs[0].iter().zip(&runs[1]).position(|(a, b)| a != b).unwrap_or(runs[0].len().min(runs[1].len()));
+        anyhow::bail!(
+            "runs differ ({} and {} bytes, first difference at byte {})",
+            runs[0].len(),
+            runs[1].len(),
+            first
+        );
+    }
+    println!("PASS: both runs produced the same {} WAV bytes", runs[0].len());
+    Ok(())
+}
+
+/// Synthesize stdin line by line as it arrives, appending each line's audio to stream.wav
+fn run_stdin_stream(
+    text_to_speech: &mut TextToSpeech,
+    args: &Args,
+    lang: &str,
+    style: &Style,
+    total_step: usize,
+) -> Result<()> {
+    // A bounded channel: reading stdin pauses while a few lines are waiting for synthesis
+    let (tx, rx) = std::sync::mpsc::sync_channel(4);
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines().map_while(Result::ok) {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let opts = StreamOptions {
+        lang: lang.to_string(),
+        total_step,
+        speed: args.speed,
+        crossfade_secs: args.stream_crossfade_ms / 1000.0,
+        ..StreamOptions::default()
+    };
+    let path = PathBuf::from(&args.save_dir).join("stream.wav");
+    let mut sink = WavSink::create(&path, text_to_speech.sample_rate, pcm_options(args))?;
+    let mut total = 0.0;
+    for buffer in text_to_speech.synthesize_channel(rx, style, opts) {
+        match buffer {
+            Ok(buffer) => {
+                sink.write_chunk(&buffer.samples)?;
+                total += buffer.duration_secs();
+                println!("Streamed {:.2} s (total {:.2} s)", buffer.duration_secs(), total);
+            }
+            Err(e) => eprintln!("Warning: failed to synthesize a line: {:#}", e),
+        }
+    }
+    sink.finish()?;
+    println!("Saved: {}", path.display());
+    Ok(())
+}
+
+/// Synthesize `sentence` with each voice style file in --voices-dir, one output per voice
+fn run_audition(
+    text_to_speech: &mut TextToSpeech,
+    args: &Args,
+    sentence: &str,
+    lang: &str,
+    total_step: usize,
+) -> Result<()> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(&args.voices_dir)
+        .with_context(|| format!("Failed to read voices directory {}", args.voices_dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        // The voice library index lives alongside the styles but is not one
+        .filter(|p| p.extension().is_some_and(|e| e == "json") && p.file_name().is_some_and(|n| n != "index.json"))
+        .collect();
+    paths.sort();
+    if paths.is_empty() {
+        anyhow::bail!("No voice style files found in {}", args.voices_dir.display());
+    }
+
+    let sample_rate = text_to_speech.sample_rate;
+    let mut failed: Vec<(String, String)> = Vec::new();
+    for (i, path) in paths.iter().enumerate() {
+        let voice_path = path.to_string_lossy().into_owned();
+        let voice_name = sanitize_filename(&path.file_stem().unwrap_or_default().to_string_lossy(), 50);
+        println!("\n[{}/{}] Voice {}", i + 1, paths.len(), voice_name);
+
+        let style = match load_voice_style(std::slice::from_ref(&voice_path), false) {
+            Ok(style) => style,
+            Err(e) => {
+                println!("Failed to load: {:#}", e);
+                failed.push((voice_path, format!("{:#}", e)));
+                continue;
+            }
+        };
+        let (wav, duration) = match text_to_speech.call(sentence, lang, &style, total_step, args.speed, 0.3, None, None) {
+            Ok(output) => output,
+            Err(e) => {
+                println!("Failed to synthesize: {:#}", e);
+                failed.push((voice_path, format!("{:#}", e)));
+                continue;
+            }
+        };
+        let wav = wav[..((sample_rate as f32 * duration) as usize).min(wav.len())].to_vec();
+        let output_path = PathBuf::from(&args.save_dir).join(format!("{}.{}", voice_name, args.output_format));
+        let item = ItemInfo { text: sentence, lang, voice_style: &voice_path };
+        save_output(&output_path, &postprocess(wav, args, sample_rate), args, sample_rate, Some(&item))?;
+    }
+
+    println!("\nAuditioned {} of {} voices", paths.len() - failed.len(), paths.len());
+    if !failed.is_empty() {
+        println!("Voice styles that failed to load or synthesize:");
+        for (path, error) in &failed {
+            println!("  {}: {}", path, error);
+        }
+    }
+    Ok(())
+}
+
+/// One synthesis run as recorded by --request-log
+#[derive(Serialize, Deserialize)]
+struct RequestRecord {
+    text: Vec<String>,
+    lang: Vec<String>,
+    voice_style: Vec<String>,
+    #[serde(default)]
+    mix_components: Vec<String>,
+    #[serde(default)]
+    mix_weights: Vec<f32>,
+    #[serde(default)]
+    speaker_embedding: Vec<PathBuf>,
+    #[serde(default)]
+    style_scale: Vec<f32>,
+    total_step: usize,
+    speed: f32,
+    seed: Option<u64>,
+    #[serde(default)]
+    seed_per_item: bool,
+    batch: bool,
+    /// Batch items were synthesized one at a time (--continue-on-error)
+    #[serde(default)]
+    each: bool,
+    #[serde(default)]
+    max_batch: Option<usize>,
+    latent_rounding: String,
+    #[serde(default)]
+    chunk_compress: Option<i32>,
+    #[serde(default)]
+    latent_dim: Option<i32>,
+    #[serde(default)]
+    chunk_pauses: Option<String>,
+    #[serde(default)]
+    normalize_text: bool,
+    #[serde(default)]
+    skip_normalization: bool,
+    #[serde(default)]
+    out_of_range_id: Option<i64>,
+    /// Applied at load time, so a replay must pass the same --indexer-patch
+    #[serde(default)]
+    indexer_patch: Option<PathBuf>,
+    #[serde(default)]
+    strict_text: bool,
+    #[serde(default)]
+    max_wav_seconds: Option<f32>,
+    #[serde(default)]
+    on_overlong: String,
+    #[serde(default)]
+    latent_tile: Option<usize>,
+    #[serde(default)]
+    latent_tile_overlap: usize,
+    #[serde(default)]
+    max_latent_len: Option<usize>,
+    #[serde(default)]
+    init_latent: Option<PathBuf>,
+    onnx_dir: String,
+    /// Runs of the same invocation before this one; a shared seeded RNG had already advanced past them
+    run: usize,
+}
+
+fn append_request(path: &Path, record: &RequestRecord) -> Result<()> {
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .with_context(|| format!("Failed to append to request log {}", path.display()))
+}
+
+/// Synthesize one recorded run along the same path (single, batch, sub-batches or per item)
+fn synthesize_request(
+    text_to_speech: &mut TextToSpeech,
+    record: &RequestRecord,
+    style: &Style,
+    initial_latent: Option<&Array3<f32>>,
+) -> Result<Vec<Vec<f32>>> {
+    let sample_rate = text_to_speech.sample_rate;
+    let bsz = record.voice_style.len();
+    if !record.batch {
+        let (wav, duration) = text_to_speech.call(
+            &record.text[0],
+            &record.lang[0],
+            style,
+            record.total_step,
+            record.speed,
+            0.3,
+            initial_latent.cloned(),
+            None,
+        )?;
+        return Ok(split_outputs(&wav, &[duration], bsz, false, sample_rate));
+    }
+    if record.each {
+        return text_to_speech
+            .batch_each(&record.text, &record.lang, style, record.total_step, record.speed)
+            .into_iter()
+            .map(|r| r.map(|(w, d)| w[..((sample_rate as f32 * d) as usize).min(w.len())].to_vec()))
+            .collect();
+    }
+    let chunk = record.max_batch.unwrap_or(bsz);
+    let mut outputs = Vec::with_capacity(bsz);
+    for start in (0..bsz).step_by(chunk) {
+        let end = (start + chunk).min(bsz);
+        let (wav, duration) = text_to_speech.batch(
+            &record.text[start..end],
+            &record.lang[start..end],
+            &style.slice(start..end),
+            record.total_step,
+            record.speed,
+            initial_latent.cloned(),
+        )?;
+        outputs.extend(split_outputs(&wav, &duration, end - start, true, sample_rate));
+    }
+    Ok(outputs)
+}
+
+/// Re-run each request of a --request-log file, writing `replay_<line>_<item>` outputs
+///
+/// A request from a later run of a seeded invocation is preceded by that many discarded
+/// runs, so the shared RNG reaches the same state and the output matches bit for bit.
+fn run_replay(text_to_speech: &mut TextToSpeech, args: &Args, path: &Path) -> Result<()> {
+    let log = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let sample_rate = text_to_speech.sample_rate;
+    for (line_no, line) in log.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let record: RequestRecord = serde_json::from_str(line)
+            .with_context(|| format!("Invalid request at {}:{}", path.display(), line_no + 1))?;
+        println!("\n[replay {}] {} text(s), seed {:?}, run {}", line_no + 1, record.text.len(), record.seed, record.run + 1);
+        if record.onnx_dir != args.onnx_dir {
+            eprintln!(
+                "Warning: request was recorded with --onnx-dir {}, replaying with {}",
+                record.onnx_dir, args.onnx_dir
+            );
+        }
+        if record.seed.is_none() {
+            eprintln!("Warning: request has no seed; the replay will not match the original noise");
+        }
+
+        if record.indexer_patch != args.indexer_patch {
+            anyhow::bail!(
+                "Request at {}:{} was recorded with --indexer-patch {}; replay it with the same --indexer-patch",
+                path.display(),
+                line_no + 1,
+                record.indexer_patch.as_deref().map_or("(none)".into(), |p| p.display().to_string())
+            );
+        }
+
+        text_to_speech.set_latent_rounding(parse_latent_rounding(&record.latent_rounding));
+        text_to_speech.set_chunk_compress(record.chunk_compress)?;
+        text_to_speech.set_latent_dim(record.latent_dim)?;
+        text_to_speech.set_chunk_pauses(record.chunk_pauses.as_deref().map(parse_chunk_pauses).transpose()?);
+        if record.normalize_text {
+            text_to_speech.set_text_normalizer(Box::new(EnglishNormalizer));
+        } else {
+            text_to_speech.set_text_normalizer(Box::new(NoopNormalizer));
+        }
+        text_to_speech.set_skip_normalization(record.skip_normalization);
+        text_to_speech.set_out_of_range_id(record.out_of_range_id);
+        text_to_speech.set_unmappable_policy(if record.strict_text {
+            UnmappableTextPolicy::Strict
+        } else {
+            UnmappableTextPolicy::default()
+        });
+        text_to_speech.set_max_wav_seconds(record.max_wav_seconds, parse_overlong_policy(&record.on_overlong));
+        text_to_speech.set_latent_tile(record.latent_tile, record.latent_tile_overlap)?;
+        text_to_speech.set_max_latent_len(record.max_latent_len.unwrap_or(DEFAULT_MAX_LATENT_LEN));
+        let initial_latent: Option<Array3<f32>> = match &record.init_latent {
+            Some(latent_path) => Some(read_npy(latent_path).with_context(|| {
+                format!("Failed to read initial latent from {}", latent_path.display())
+            })?),
+            None => None,
+        };
+        let style = build_style(
+            &record.voice_style,
+            &record.mix_components,
+            &record.mix_weights,
+            &record.speaker_embedding,
+            &record.style_scale,
+            false,
+        )?;
+
+        match record.seed {
+            Some(seed) if record.seed_per_item => text_to_speech.set_item_seed(Some(seed)),
+            seed => {
+                text_to_speech.set_seed(seed);
+                if seed.is_some() {
+                    for _ in 0..record.run {
+                        synthesize_request(text_to_speech, &record, &style, initial_latent.as_ref())?;
+                    }
+                }
+            }
+        }
+
+        for (i, wav) in synthesize_request(text_to_speech, &record, &style, initial_latent.as_ref())?.into_iter().enumerate() {
+            let output_path =
+                PathBuf::from(&args.save_dir).join(format!("replay_{}_{}.{}", line_no + 1, i, args.output_format));
+            save_output(&output_path, &postprocess(wav, args, sample_rate), args, sample_rate, None)?;
+        }
+    }
+    Ok(())
+}
+
+/// Synthesize raw text id sequences from a JSON file, bypassing normalization and the indexer
+fn run_ids_file(
+    text_to_speech: &mut TextToSpeech,
+    args: &Args,
+    path: &Path,
+    style: &Style,
+    total_step: usize,
+) -> Result<()> {
+    let text_ids: Vec<Vec<i64>> = read_json_file(path)?;
+    let bsz = text_ids.len();
+    let style = match style.ttl.dim().0 {
+        1 => concat_styles(&vec![style.clone(); bsz])?,
+        n if n == bsz => style.clone(),
+        n => anyhow::bail!("{} has {} id sequences but {} voice styles were given", path.display(), bsz, n),
+    };
+    let sample_rate = text_to_speech.sample_rate;
+
+    for n in 0..args.n_test {
+        println!("\n[{}/{}] Synthesizing {} id sequence(s) from {}", n + 1, args.n_test, bsz, path.display());
+        let (wav, duration) = timer("Generating speech from ids", || {
+            text_to_speech.call_from_ids(&text_ids, &style, total_step, args.speed)
+        })?;
+        for (i, output) in split_outputs(&wav, &duration, bsz, true, sample_rate).into_iter().enumerate() {
+            let output_path = PathBuf::from(&args.save_dir).join(format!("ids_{}_{}.{}", i, n + 1, args.output_format));
+            save_output(&output_path, &postprocess(output, args, sample_rate), args, sample_rate, None)?;
+        }
+    }
+    Ok(())
+}
+
+/// Synthesize the first text at each step count with the same noise seed and report latency
+fn run_step_sweep(
+    text_to_speech: &mut TextToSpeech,
+    args: &Args,
+    text: &str,
+    lang: &str,
+    style: &Style,
+    step_counts: &[usize],
+) -> Result<()> {
+    let seed: u64 = args.seed.unwrap_or_else(rand::random);
+    println!("Sweeping step counts {:?} with seed {}", step_counts, seed);
+
+    let sample_rate = text_to_speech.sample_rate;
+    let mut latencies = Vec::new();
+
+    for &steps in step_counts {
+        text_to_speech.set_seed(Some(seed));
+
+        let start = Instant::now();
+        let (wav, duration) = text_to_speech.call(text, lang, style, steps, args.speed, 0.3, None, None)?;
+        let elapsed = start.elapsed().as_secs_f64();
+        latencies.push((steps, elapsed));
+
+        let actual_len = ((sample_rate as f32 * duration) as usize).min(wav.len());
+        let fname = format!("{}_steps{}.wav", sanitize_filename(text, 20), steps);
+        let output_path = PathBuf::from(&args.save_dir).join(&fname);
+        write_wav_file_with_options(&output_path, &wav[..actual_len], sample_rate, pcm_options(args))?;
+        println!("  steps={:>3}: {:.3} sec -> {}", steps, elapsed, output_path.display());
+    }
+
+    println!("\n=== Step sweep latency ===");
+    for (steps, elapsed) in latencies {
+        println!("  {:>3} steps: {:.3} sec", steps, elapsed);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use supertonic::math::wav_length;
+
+    #[test]
+    fn empty_audio_is_skipped_or_padded() {
+        // A microsecond at 44.1 kHz rounds down to no samples at all
+        let wav = vec![0.5f32; wav_length(1e-6, 44100)];
+        assert!(wav.is_empty());
+        assert!(resolve_empty_audio(&wav, "skip", 44100).is_none());
+        let padded = resolve_empty_audio(&wav, "silence", 44100).unwrap();
+        assert_eq!(padded.len(), 441);
+        assert!(padded.iter().all(|&v| v == 0.0));
+    }
+
+    #[test]
+    fn non_empty_audio_is_passed_through() {
+        let wav = [0.25f32; 3];
+        for policy in ["skip", "silence"] {
+            assert!(matches!(resolve_empty_audio(&wav, policy, 44100), Some(Cow::Borrowed(w)) if w == wav));
+        }
+    }
+}
@@ -0,0 +1,249 @@
+// ============================================================================
+// Pure Math Helpers - Masking and latent sizing
+// ============================================================================
+//
+// Nothing in this module touches files, ONNX Runtime, or std-only APIs; it
+// relies on `core` and ndarray alone, and is all that `--no-default-features`
+// builds, so it can be reused in constrained builds.
+
+use core::fmt;
+use ndarray::Array3;
+
+/// Default upper bound on the latent length (frames) of one inference
+///
+/// About 18 minutes of audio with the released models (3072 samples per frame at 44.1 kHz).
+pub const DEFAULT_MAX_LATENT_LEN: usize = 1 << 14;
+
+/// Build a (bsz, 1, max_len) mask with ones up to each length
+///
+/// `max_len` defaults to the longest length; lengths beyond it are truncated.
+pub fn length_to_mask(lengths: &[usize], max_len: Option<usize>) -> Array3<f32> {
+    let bsz = lengths.len();
+    let max_len = max_len.unwrap_or_else(|| *lengths.iter().max().unwrap_or(&0));
+
+    let mut mask = Array3::<f32>::zeros((bsz, 1, max_len));
+    for (i, &len) in lengths.iter().enumerate() {
+        for j in 0..len.min(max_len) {
+            mask[[i, 0, j]] = 1.0;
+        }
+    }
+    mask
+}
+
+pub fn get_text_mask(text_ids_lengths: &[usize]) -> Array3<f32> {
+    let max_len = *text_ids_lengths.iter().max().unwrap_or(&0);
+    length_to_mask(text_ids_lengths, Some(max_len))
+}
+
+/// Number of waveform samples covering `duration` seconds (truncated)
+pub fn wav_length(duration: f32, sample_rate: i32) -> usize {
+    (duration * sample_rate as f32) as usize
+}
+
+/// How a waveform length that is not a multiple of the chunk size maps to latent frames
+///
+/// The released Supertonic models are trained with `Ceil`, which keeps the trailing
+/// partial chunk. `Floor` drops it and `Round` keeps it only from half a chunk on; use
+/// them only for model variants trained that way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LatentRounding {
+    #[default]
+    Ceil,
+    Floor,
+    Round,
+}
+
+/// Number of latent frames for `wav_len` samples under the given rounding
+///
+/// Panics if `chunk_size` is zero; [`compute_lengths`] clamps it to at least one.
+pub fn latent_length(wav_len: usize, chunk_size: usize, rounding: LatentRounding) -> usize {
+    match rounding {
+        LatentRounding::Ceil => wav_len.div_ceil(chunk_size),
+        LatentRounding::Floor => wav_len / chunk_size,
+        LatentRounding::Round => (wav_len + chunk_size / 2) / chunk_size,
+    }
+}
+
+/// Latent and waveform sizes derived from predicted durations
+#[derive(Debug, Clone, PartialEq)]
+pub struct LengthInfo {
+    /// Waveform samples per latent frame (`base_chunk_size * chunk_compress`)
+    pub chunk_size: usize,
+    /// Channels of the compressed latent (`latent_dim * chunk_compress`)
+    pub latent_dim: usize,
+    /// Padded latent length shared by the whole batch
+    pub latent_len: usize,
+    /// Unpadded latent length of each item
+    pub latent_lengths: Vec<usize>,
+    /// Waveform length of each item in samples
+    pub wav_lengths: Vec<usize>,
+}
+
+/// A duration that cannot be turned into a latent of acceptable size
+#[derive(Debug, Clone, PartialEq)]
+pub enum LengthError {
+    /// NaN, infinite or negative duration of batch item `index`
+    InvalidDuration { index: usize, duration: f32 },
+    /// Latent length of batch item `index` exceeds `max_latent_len`
+    TooLong { index: usize, duration: f32, latent_len: usize, max_latent_len: usize },
+}
+
+impl fmt::Display for LengthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LengthError::InvalidDuration { index, duration } => {
+                write!(f, "Invalid duration {} s for batch item {}", duration, index)
+            }
+            LengthError::TooLong { index, duration, latent_len, max_latent_len } => write!(
+                f,
+                "Duration {} s of batch item {} needs {} latent frames, more than the maximum of {}",
+                duration, index, latent_len, max_latent_len
+            ),
+        }
+    }
+}
+
+impl core::error::Error for LengthError {}
+
+/// Compute the latent shape and per-item lengths for a batch of durations
+///
+/// Fails instead of sizing a huge allocation when a duration is not finite and
+/// non-negative, or needs more than `max_latent_len` latent frames.
+pub fn compute_lengths(
+    duration: &[f32],
+    sample_rate: i32,
+    base_chunk_size: i32,
+    chunk_compress: i32,
+    latent_dim: i32,
+    rounding: LatentRounding,
+    max_latent_len: usize,
+) -> Result<LengthInfo, LengthError> {
+    let chunk_size = (base_chunk_size as usize).saturating_mul(chunk_compress as usize).max(1);
+    // Bound the sample count before converting, so the cast below can neither saturate nor wrap
+    let max_wav_len = max_latent_len.saturating_add(1).saturating_mul(chunk_size);
+    for (index, &d) in duration.iter().enumerate() {
+        if !d.is_finite() || d < 0.0 {
+            return Err(LengthError::InvalidDuration { index, duration: d });
+        }
+        let samples = f64::from(d) * f64::from(sample_rate);
+        let latent_len = if samples >= max_wav_len as f64 {
+            (samples / chunk_size as f64).ceil() as usize
+        } else {
+            latent_length(samples as usize, chunk_size, rounding)
+        };
+        if latent_len > max_latent_len {
+            return Err(LengthError::TooLong { index, duration: d, latent_len, max_latent_len });
+        }
+    }
+
+    let max_dur = duration.iter().fold(0.0f32, |a, &b| a.max(b));
+    let wav_lengths: Vec<usize> = duration.iter().map(|&d| wav_length(d, sample_rate)).collect();
+    let latent_lengths = wav_lengths
+        .iter()
+        .map(|&len| latent_length(len, chunk_size, rounding))
+        .collect();
+
+    Ok(LengthInfo {
+        chunk_size,
+        latent_dim: (latent_dim * chunk_compress) as usize,
+        latent_len: latent_length(wav_length(max_dur, sample_rate), chunk_size, rounding),
+        latent_lengths,
+        wav_lengths,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mask_truncates_lengths_beyond_max_len() {
+        let mask = length_to_mask(&[2, 5], Some(3));
+        assert_eq!(mask.shape(), &[2, 1, 3]);
+        assert_eq!(mask.as_slice().unwrap(), &[1.0, 1.0, 0.0, 1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn mask_of_zero_length_is_all_zeros() {
+        let mask = length_to_mask(&[0, 2], None);
+        assert_eq!(mask.shape(), &[2, 1, 2]);
+        assert_eq!(mask.as_slice().unwrap(), &[0.0, 0.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn mask_of_empty_input_is_empty() {
+        assert_eq!(length_to_mask(&[], None).shape(), &[0, 1, 0]);
+    }
+
+    #[test]
+    fn mask_defaults_to_longest_length() {
+        let mask = length_to_mask(&[1, 3], None);
+        assert_eq!(mask.shape(), &[2, 1, 3]);
+        assert_eq!(mask.as_slice().unwrap(), &[1.0, 0.0, 0.0, 1.0, 1.0, 1.0]);
+        assert_eq!(get_text_mask(&[1, 3]), mask);
+    }
+
+    #[test]
+    fn latent_length_of_exact_multiple_ignores_rounding() {
+        for rounding in [LatentRounding::Ceil, LatentRounding::Floor, LatentRounding::Round] {
+            assert_eq!(latent_length(3 * 512, 512, rounding), 3);
+            assert_eq!(latent_length(0, 512, rounding), 0);
+        }
+    }
+
+    #[test]
+    fn latent_length_of_partial_chunk_follows_rounding() {
+        // A quarter chunk over: only Ceil keeps it
+        assert_eq!(latent_length(3 * 512 + 128, 512, LatentRounding::Ceil), 4);
+        assert_eq!(latent_length(3 * 512 + 128, 512, LatentRounding::Floor), 3);
+        assert_eq!(latent_length(3 * 512 + 128, 512, LatentRounding::Round), 3);
+        // Half a chunk over: Round keeps it too
+        assert_eq!(latent_length(3 * 512 + 256, 512, LatentRounding::Ceil), 4);
+        assert_eq!(latent_length(3 * 512 + 256, 512, LatentRounding::Floor), 3);
+        assert_eq!(latent_length(3 * 512 + 256, 512, LatentRounding::Round), 4);
+    }
+
+    #[test]
+    #[should_panic]
+    fn latent_length_rejects_zero_chunk_size() {
+        latent_length(100, 0, LatentRounding::Ceil);
+    }
+
+    #[test]
+    fn compute_lengths_clamps_zero_chunk_size() {
+        let info = compute_lengths(&[1.0], 100, 0, 6, 24, LatentRounding::Ceil, 1000).unwrap();
+        assert_eq!(info.chunk_size, 1);
+        assert_eq!(info.latent_len, 100);
+    }
+
+    fn lengths(duration: f32, max_latent_len: usize) -> Result<LengthInfo, LengthError> {
+        // Released model geometry: 512 * 6 = 3072 samples per latent frame
+        compute_lengths(&[duration], 44100, 512, 6, 24, LatentRounding::Ceil, max_latent_len)
+    }
+
+    #[test]
+    fn compute_lengths_rejects_non_finite_durations() {
+        for d in [f32::INFINITY, f32::NEG_INFINITY, f32::NAN, -1.0] {
+            assert!(
+                matches!(lengths(d, DEFAULT_MAX_LATENT_LEN), Err(LengthError::InvalidDuration { index: 0, .. })),
+                "{d}"
+            );
+        }
+    }
+
+    #[test]
+    fn compute_lengths_rejects_huge_durations() {
+        let err = lengths(f32::MAX, DEFAULT_MAX_LATENT_LEN).unwrap_err();
+        assert!(matches!(err, LengthError::TooLong { index: 0, max_latent_len: DEFAULT_MAX_LATENT_LEN, .. }));
+    }
+
+    #[test]
+    fn compute_lengths_accepts_up_to_max_latent_len() {
+        // 99.5 frames rounds up to exactly the limit; 100.5 frames is one over
+        let info = lengths(99.5 * 3072.0 / 44100.0, 100).unwrap();
+        assert_eq!(info.latent_len, 100);
+        assert_eq!(info.latent_lengths, vec![100]);
+        assert_eq!(info.latent_dim, 144);
+        assert!(matches!(lengths(100.5 * 3072.0 / 44100.0, 100), Err(LengthError::TooLong { latent_len: 101, .. })));
+    }
+}